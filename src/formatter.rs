@@ -1,8 +1,40 @@
 use serde_json::{json, Value};
 use regex::Regex;
+use std::borrow::Cow;
+use std::sync::LazyLock;
 
 /// Maximum size of a block in the Notion API
-const MAX_BLOCK_SIZE: usize = 2000;
+pub const MAX_BLOCK_SIZE: usize = 2000;
+
+/// Headers (levels 1-3) used as preferred split points, compiled once
+static HEADER_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?m)^(#{1,3}\s.+)$").unwrap());
+
+/// Numbered list item prefix such as `1. ` or `42. `, compiled once
+static NUMBERED_LIST_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\d+\.\s").unwrap());
+
+/// Chunk size used to split content before formatting
+///
+/// Defaults to [`MAX_BLOCK_SIZE`]; `JOTDOWN_MAX_BLOCK_SIZE` can lower it for
+/// smaller chunks but never raise it past Notion's limit.
+pub fn max_block_size() -> usize {
+    block_size_from(std::env::var("JOTDOWN_MAX_BLOCK_SIZE").ok())
+}
+
+fn block_size_from(value: Option<String>) -> usize {
+    value
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|size| *size > 0)
+        .map_or(MAX_BLOCK_SIZE, |size| size.min(MAX_BLOCK_SIZE))
+}
+
+/// Length of the page summary stored in the database Content property
+pub const SUMMARY_MAX_CHARS: usize = 200;
+
+/// Deepest list nesting Notion accepts when children are sent in one request
+const MAX_LIST_DEPTH: usize = 2;
+
+/// Indentation used for each level of nested list items in Markdown output
+const LIST_INDENT: &str = "  ";
 
 /// Split content into chunks to respect Notion API limits
 ///
@@ -15,60 +47,39 @@ const MAX_BLOCK_SIZE: usize = 2000;
 ///
 /// * `Vec<String>` - List of text chunks
 pub fn split_content(text: &str, max_length: usize) -> Vec<String> {
+    let text = normalize_line_endings(text);
+    let text = text.as_ref();
     if text.len() <= max_length {
         return vec![text.to_string()];
     }
     
     // Try to split by headers
-    let header_regex = Regex::new(r"(?m)^(#{1,3}\s.+)$").unwrap();
-    let headers: Vec<_> = header_regex.find_iter(text).collect();
+    let headers: Vec<_> = HEADER_REGEX.find_iter(text).collect();
     
     if headers.is_empty() {
         // No headers, use simple method
         return simple_split(text, max_length);
     }
     
+    // Sections start at each header, text before the first header is a section of its own
+    let mut starts: Vec<usize> = headers.iter().map(|header| header.start()).collect();
+    if starts[0] > 0 {
+        starts.insert(0, 0);
+    }
+    let ends = starts.iter().skip(1).copied().chain(std::iter::once(text.len()));
+    let sections = starts.iter().zip(ends).map(|(&start, end)| &text[start..end]);
+    
+    // Pack whole sections into chunks while they fit
     let mut parts = Vec::new();
-    let mut last_pos = 0;
     let mut current_chunk = String::new();
-    
-    // Process headers as splitting points
-    for (i, header_match) in headers.iter().enumerate() {
-        // Get content from last point to current header
-        if i > 0 {
-            let header_content = &text[last_pos..header_match.start()];
-            
-            // If adding this header section would exceed max size,
-            // start a new chunk
-            if current_chunk.len() + header_content.len() > max_length {
-                parts.push(current_chunk.clone());
-                current_chunk = header_content.to_string();
-            } else {
-                current_chunk.push_str(header_content);
-            }
+    for section in sections {
+        if !current_chunk.is_empty() && current_chunk.len() + section.len() > max_length {
+            parts.push(std::mem::take(&mut current_chunk));
         }
-        
-        // First header or after a split
-        if current_chunk.is_empty() {
-            current_chunk = text[header_match.start()..].to_string();
-            // If still too large, we'll need to split it later
-        }
-        
-        last_pos = header_match.start();
+        current_chunk.push_str(section);
     }
-    
-    // Add final chunk
-    if last_pos < text.len() {
-        let final_content = &text[last_pos..];
-        if current_chunk.len() + final_content.len() > max_length {
-            parts.push(current_chunk.clone());
-            parts.push(final_content.to_string());
-        } else {
-            current_chunk.push_str(final_content);
-            parts.push(current_chunk.clone());
-        }
-    } else if !current_chunk.is_empty() {
-        parts.push(current_chunk.clone());
+    if !current_chunk.is_empty() {
+        parts.push(current_chunk);
     }
     
     // If any chunk is still too large, split it further
@@ -106,13 +117,7 @@ fn simple_split(text: &str, max_length: usize) -> Vec<String> {
     // If text is just a repeating character (like in tests) with no structure,
     // split it into equal chunks first
     if text.chars().all(|c| c == text.chars().next().unwrap()) && text.len() > max_length {
-        let mut remaining = text.to_string();
-        while !remaining.is_empty() {
-            let split_point = std::cmp::min(max_length, remaining.len());
-            chunks.push(remaining[..split_point].to_string());
-            remaining = remaining[split_point..].to_string();
-        }
-        return chunks;
+        return split_at_char_boundaries(text, max_length);
     }
     
     for line in text.split('\n') {
@@ -185,20 +190,245 @@ fn simple_split(text: &str, max_length: usize) -> Vec<String> {
         if chunk.len() <= max_length {
             final_chunks.push(chunk);
         } else {
-            let mut remaining = chunk;
-            while !remaining.is_empty() {
-                let split_point = std::cmp::min(max_length, remaining.len());
-                final_chunks.push(remaining[..split_point].to_string());
-                remaining = remaining[split_point..].to_string();
-            }
+            final_chunks.extend(split_at_char_boundaries(&chunk, max_length));
         }
     }
     
     final_chunks
 }
 
+/// Hard-split text into pieces of at most `max_length` bytes
+///
+/// Split points are moved back to the nearest UTF-8 code point boundary so
+/// multi-byte characters are never cut in half. A piece only exceeds
+/// `max_length` when a single character is wider than the limit itself.
+///
+/// # Arguments
+///
+/// * `text` - Text to be split
+/// * `max_length` - Maximum length of each piece in bytes
+///
+/// # Returns
+///
+/// * `Vec<String>` - List of text pieces
+fn split_at_char_boundaries(text: &str, max_length: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut remaining = text;
+    while !remaining.is_empty() {
+        let mut split_point = remaining.floor_char_boundary(max_length);
+        if split_point == 0 {
+            // A single character is wider than max_length, keep it whole
+            split_point = remaining.ceil_char_boundary(1);
+        }
+        let (piece, rest) = remaining.split_at(split_point);
+        pieces.push(piece.to_string());
+        remaining = rest;
+    }
+    pieces
+}
+
+/// Metadata read from a YAML frontmatter block
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Frontmatter {
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// Key of a `key: value` frontmatter line, compiled once
+static FRONTMATTER_KEY_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^([A-Za-z_][\w-]*):(.*)$").unwrap());
+
+/// Split leading YAML frontmatter (`---` ... `---`) off markdown
+///
+/// Only the `title` and `tags` keys are read; tags may be an inline list
+/// (`[a, b]`), comma separated, or a `- item` list on the following lines.
+/// Text that doesn't look like `key: value` frontmatter is left untouched,
+/// so a divider at the top of a note stays a divider.
+///
+/// # Arguments
+///
+/// * `text` - Markdown text
+///
+/// # Returns
+///
+/// * `(Frontmatter, &str)` - Parsed metadata and the markdown after the frontmatter
+pub fn split_frontmatter(text: &str) -> (Frontmatter, &str) {
+    let no_frontmatter = (Frontmatter::default(), text);
+    let Some(rest) = text.strip_prefix("---\n").or_else(|| text.strip_prefix("---\r\n")) else {
+        return no_frontmatter;
+    };
+    let mut offset = 0;
+    let mut yaml_lines = Vec::new();
+    let body = loop {
+        let Some(line) = rest[offset..].split_inclusive('\n').next() else {
+            return no_frontmatter;
+        };
+        offset += line.len();
+        let line = line.trim_end();
+        if line == "---" || line == "..." {
+            break &rest[offset..];
+        }
+        let valid = line.trim().is_empty()
+            || line.starts_with('#')
+            || line.starts_with(char::is_whitespace)
+            || line.starts_with("- ")
+            || FRONTMATTER_KEY_REGEX.is_match(line);
+        if !valid {
+            return no_frontmatter;
+        }
+        yaml_lines.push(line);
+    };
+
+    let mut frontmatter = Frontmatter::default();
+    let mut in_tags = false;
+    for line in yaml_lines {
+        if let Some(captures) = FRONTMATTER_KEY_REGEX.captures(line) {
+            let value = captures[2].trim();
+            in_tags = &captures[1] == "tags";
+            match &captures[1] {
+                "title" if !value.is_empty() => frontmatter.title = Some(unquote(value).to_string()),
+                "tags" => frontmatter.tags.extend(
+                    value
+                        .trim_start_matches('[')
+                        .trim_end_matches(']')
+                        .split(',')
+                        .map(|tag| unquote(tag.trim()).to_string())
+                        .filter(|tag| !tag.is_empty()),
+                ),
+                _ => {}
+            }
+        } else if in_tags && let Some(tag) = line.trim().strip_prefix("- ") {
+            let tag = unquote(tag.trim());
+            if !tag.is_empty() {
+                frontmatter.tags.push(tag.to_string());
+            }
+        }
+    }
+    (frontmatter, body)
+}
+
+/// Strip one pair of matching YAML quotes
+fn unquote(value: &str) -> &str {
+    ['"', '\'']
+        .iter()
+        .find_map(|&quote| value.strip_prefix(quote).and_then(|v| v.strip_suffix(quote)))
+        .unwrap_or(value)
+}
+
+/// `[^label]: definition` line of a footnote, compiled once
+static FOOTNOTE_DEFINITION_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\[\^([^\]\s]+)\]:\s*(.*)$").unwrap());
+
+/// `[^label]` reference to a footnote, compiled once
+static FOOTNOTE_REFERENCE_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[\^([^\]\s]+)\]").unwrap());
+
+/// Take the footnotes out of Markdown text
+///
+/// Definitions (`[^label]: text`, continued by indented lines) are removed
+/// and references to them become `[n]` markers, numbered in order of first
+/// reference. References without a definition and anything inside code are
+/// left as they are.
+///
+/// # Arguments
+///
+/// * `text` - Markdown text to be converted
+///
+/// # Returns
+///
+/// * `(String, Vec<Value>)` - Text without footnotes, and a divider followed by
+///   one numbered item per definition to append at the bottom of the page
+pub fn split_footnotes(text: &str) -> (String, Vec<Value>) {
+    if !text.contains("[^") {
+        return (text.to_string(), Vec::new());
+    }
+    let text = normalize_line_endings(text);
+    let text = text.as_ref();
+    let mut definitions: Vec<(String, String)> = Vec::new();
+    let mut body = Vec::new();
+    let mut in_code = false;
+    let mut in_definition = false;
+    for line in text.split('\n') {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        }
+        if in_definition && (line.starts_with("    ") || line.starts_with('\t')) && !line.trim().is_empty() {
+            let (_, definition) = definitions.last_mut().unwrap();
+            definition.push(' ');
+            definition.push_str(line.trim());
+            continue;
+        }
+        in_definition = false;
+        if !in_code && let Some(captures) = FOOTNOTE_DEFINITION_REGEX.captures(line) {
+            let label = captures[1].to_string();
+            if !definitions.iter().any(|(existing, _)| *existing == label) {
+                definitions.push((label, captures[2].trim().to_string()));
+                in_definition = true;
+            }
+            continue;
+        }
+        body.push(line);
+    }
+    if definitions.is_empty() {
+        return (text.to_string(), Vec::new());
+    }
+
+    // Labels in the order they are first referenced
+    let mut numbered: Vec<String> = Vec::new();
+    let mut converted = Vec::with_capacity(body.len());
+    in_code = false;
+    for line in body {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        }
+        if in_code || !line.contains("[^") {
+            converted.push(line.to_string());
+            continue;
+        }
+        // Odd pieces are inside `code` spans
+        let pieces: Vec<String> = line
+            .split('`')
+            .enumerate()
+            .map(|(index, piece)| {
+                if index % 2 == 1 {
+                    return piece.to_string();
+                }
+                FOOTNOTE_REFERENCE_REGEX
+                    .replace_all(piece, |captures: &regex::Captures| {
+                        let label = &captures[1];
+                        if !definitions.iter().any(|(defined, _)| defined == label) {
+                            return captures[0].to_string();
+                        }
+                        let number = match numbered.iter().position(|seen| seen == label) {
+                            Some(position) => position + 1,
+                            None => {
+                                numbered.push(label.to_string());
+                                numbered.len()
+                            }
+                        };
+                        format!("[{}]", number)
+                    })
+                    .to_string()
+            })
+            .collect();
+        converted.push(pieces.join("`"));
+    }
+
+    // Referenced footnotes by number, then any that were never referenced
+    definitions.sort_by_key(|(label, _)| numbered.iter().position(|seen| seen == label).unwrap_or(usize::MAX));
+    let mut notes = vec![json!({ "type": "divider", "divider": {} })];
+    notes.extend(definitions.iter().map(|(_, definition)| {
+        json!({
+            "type": "numbered_list_item",
+            "numbered_list_item": { "rich_text": parse_inline(definition) }
+        })
+    }));
+    (converted.join("\n"), notes)
+}
+
 /// Convert Markdown text into Notion blocks
 ///
+/// Footnotes are not handled here; take them out of the whole document with
+/// [`split_footnotes`] before splitting it into chunks.
+///
 /// # Arguments
 ///
 /// * `text` - Markdown text to be converted
@@ -207,49 +437,97 @@ fn simple_split(text: &str, max_length: usize) -> Vec<String> {
 ///
 /// * `Vec<Value>` - List of Notion blocks
 pub fn format_for_notion(text: &str) -> Vec<Value> {
+    format_blocks(&normalize_line_endings(text))
+}
+
+/// Turn Windows (`\r\n`) and old Mac (`\r`) line endings into `\n`
+fn normalize_line_endings(text: &str) -> Cow<'_, str> {
+    if text.contains('\r') {
+        Cow::Owned(text.replace("\r\n", "\n").replace('\r', "\n"))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Convert Markdown text with `\n` line endings into Notion blocks
+fn format_blocks(text: &str) -> Vec<Value> {
     let lines: Vec<&str> = text.split('\n').collect();
     let mut blocks = Vec::new();
-    let mut current_code_block: Option<Value> = None;
+    // Language and lines of the code block being collected
+    let mut current_code_block: Option<(&str, Vec<&str>)> = None;
+    // Indent widths of the currently open chain of nested list items
+    let mut list_indents: Vec<usize> = Vec::new();
     let mut i = 0;
     
     while i < lines.len() {
         let line = lines[i].trim_end();
         i += 1;
         
+        // A code block between items of a numbered list nests under the item
+        // before it, as a top-level block it would restart Notion's numbering
+        if let Some(code_lang) = line.strip_prefix("```")
+            && current_code_block.is_none()
+            && blocks.last().is_some_and(|b: &Value| b["type"] == "numbered_list_item")
+            && let Some(close) = numbered_list_resumes_after_code(&lines[i..])
+        {
+            let body: Vec<&str> = lines[i..i + close].iter().map(|l| l.trim_end()).collect();
+            i += close + 1;
+            push_nested(&mut blocks, code_block(code_lang, &body.join("\n")), 1);
+            continue;
+        }
+        
         // Detect start of code block (```language)
-        if let Some(code_lang) = line.strip_prefix("```") {
-            if current_code_block.is_none() {
-                // Start a new code block
-                current_code_block = Some(json!({
-                    "type": "code",
-                    "code": {
-                        "rich_text": [],
-                        "language": get_valid_notion_language(code_lang.trim())
-                    }
-                }));
-                continue;
-            }
+        if let Some(code_lang) = line.strip_prefix("```")
+            && current_code_block.is_none()
+        {
+            // Start a new code block
+            current_code_block = Some((code_lang, Vec::new()));
+            continue;
         }
         
         // Detect end of code block
         if line.trim() == "```" && current_code_block.is_some() {
             // Close the current code block
-            blocks.push(current_code_block.take().unwrap());
+            let (language, code_lines) = current_code_block.take().unwrap();
+            let mut block = code_block(language, &code_lines.join("\n"));
+            if let Some(caption) = lines.get(i).and_then(|next| parse_code_caption(next)) {
+                block["code"]["caption"] = Value::Array(parse_inline(caption));
+                i += 1;
+            }
+            blocks.push(block);
             continue;
         }
         
         // Add lines to current code block
-        if let Some(ref mut code_block) = current_code_block {
-            let line_with_newline = format!("{}{}", line, "\n");
-            code_block["code"]["rich_text"].as_array_mut().unwrap().push(json!({
-                "type": "text",
-                "text": { "content": line_with_newline }
-            }));
+        if let Some((_, ref mut code_lines)) = current_code_block {
+            code_lines.push(line);
             continue;
         }
         
         // Ignore empty lines outside code blocks
         if line.trim().is_empty() {
+            // Trailing blank lines get no spacer at all
+            let Some(next_line) = lines[i..].iter().find(|l| !l.trim().is_empty()) else {
+                continue;
+            };
+            // A run of blank lines gets a single spacer
+            if blocks.last().is_some_and(is_empty_paragraph) {
+                continue;
+            }
+            // A spacer between items of the same list would restart Notion's numbering
+            if let (Some(previous), Some(next)) = (blocks.last(), list_item_block(next_line.trim_start()))
+                && previous["type"] == next["type"]
+            {
+                continue;
+            }
+            // Same for a code block that will nest under the numbered item before it
+            if blocks.last().is_some_and(|b: &Value| b["type"] == "numbered_list_item")
+                && next_line.starts_with("```")
+                && let Some(fence) = lines[i..].iter().position(|l| l.starts_with("```"))
+                && numbered_list_resumes_after_code(&lines[i + fence + 1..]).is_some()
+            {
+                continue;
+            }
             // Add a paragraph with a newline for spacing
             blocks.push(json!({
                 "type": "paragraph",
@@ -258,136 +536,1146 @@ pub fn format_for_notion(text: &str) -> Vec<Value> {
             continue;
         }
         
-        // Headers
-        if line.starts_with("# ") {
+        // A leading backslash escapes block syntax, `\# text` is a paragraph reading `# text`
+        if let Some(literal) = strip_block_escape(line) {
+            list_indents.clear();
             blocks.push(json!({
-                "type": "heading_1",
-                "heading_1": { "rich_text": [{ "text": { "content": &line[2..] } }] }
+                "type": "paragraph",
+                "paragraph": { "rich_text": parse_inline(literal) }
             }));
-        } else if line.starts_with("## ") {
+            continue;
+        }
+        
+        // Fenced code indented under a list item becomes a child of that item
+        if let Some(code_lang) = line.trim_start().strip_prefix("```")
+            && list_indents.last().is_some_and(|&top| indent_width(line) > top)
+            && let Some(close) = lines[i..].iter().position(|l| l.trim() == "```")
+        {
+            let indent = indent_width(line);
+            let body: Vec<&str> = lines[i..i + close]
+                .iter()
+                .map(|l| strip_indent(l.trim_end(), indent))
+                .collect();
+            i += close + 1;
+            let depth = list_indents.len().min(MAX_LIST_DEPTH);
+            push_nested(&mut blocks, code_block(code_lang, &body.join("\n")), depth);
+            continue;
+        }
+        
+        // List items, indented items nest under the previous shallower item
+        if let Some(item) = list_item_block(line.trim_start()) {
+            let indent = indent_width(line);
+            while list_indents.last().is_some_and(|&top| top >= indent) {
+                list_indents.pop();
+            }
+            let depth = list_indents.len().min(MAX_LIST_DEPTH);
+            push_nested(&mut blocks, item, depth);
+            list_indents.truncate(depth);
+            list_indents.push(indent);
+            continue;
+        }
+        list_indents.clear();
+        
+        // Toggles: `>>> Title` opens a collapsible section and `<<<` closes it
+        if let Some(title) = parse_toggle_start(line)
+            && let Some(close) = find_toggle_end(&lines[i..])
+        {
+            let children = format_for_notion(&lines[i..i + close].join("\n"));
+            i += close + 1;
+            let mut toggle = json!({
+                "type": "toggle",
+                "toggle": { "rich_text": parse_inline(title) }
+            });
+            if !children.is_empty() {
+                toggle["toggle"]["children"] = Value::Array(children);
+            }
+            blocks.push(toggle);
+            continue;
+        }
+        
+        // Block equations: `$$expression$$` on one line, or `$$` fences around several lines
+        if let Some(opening) = line.trim().strip_prefix("$$") {
+            if let Some(expression) = opening.strip_suffix("$$")
+                && !expression.trim().is_empty()
+            {
+                blocks.push(equation_block(expression.trim()));
+                continue;
+            }
+            if let Some(close) = lines[i..].iter().position(|l| l.trim_end().ends_with("$$")) {
+                let mut expression_lines = vec![opening];
+                expression_lines.extend(&lines[i..i + close]);
+                expression_lines.push(lines[i + close].trim_end().trim_end_matches("$$"));
+                i += close + 1;
+                blocks.push(equation_block(expression_lines.join("\n").trim()));
+                continue;
+            }
+        }
+        
+        // Blockquotes, consecutive quoted lines merge into one block
+        if let Some(first) = strip_quote_marker(line) {
+            let mut quote_lines = vec![first];
+            while let Some(next) = lines.get(i).and_then(|l| strip_quote_marker(l.trim_end())) {
+                quote_lines.push(next);
+                i += 1;
+            }
+            // `> [!NOTE]` style admonitions become callouts
+            if let Some((kind, title)) = parse_admonition(quote_lines[0]) {
+                let body: Vec<&str> = std::iter::once(title)
+                    .filter(|title| !title.is_empty())
+                    .chain(quote_lines[1..].iter().copied())
+                    .collect();
+                blocks.push(json!({
+                    "type": "callout",
+                    "callout": {
+                        "rich_text": parse_inline(&body.join("\n")),
+                        "icon": { "type": "emoji", "emoji": callout_emoji(&kind) }
+                    }
+                }));
+                continue;
+            }
             blocks.push(json!({
-                "type": "heading_2",
-                "heading_2": { "rich_text": [{ "text": { "content": &line[3..] } }] }
+                "type": "quote",
+                "quote": { "rich_text": quote_rich_text(&quote_lines.join("\n")) }
             }));
-        } else if line.starts_with("### ") {
+            continue;
+        }
+        
+        // Tables: header row, separator row, then body rows
+        if let Some(header) = parse_table_row(line)
+            && lines.get(i).is_some_and(|next| is_table_separator(next))
+        {
+            let mut rows = vec![header];
+            i += 1;
+            while let Some(row) = lines.get(i).and_then(|next| parse_table_row(next)) {
+                rows.push(row);
+                i += 1;
+            }
+            blocks.push(table_block(&rows));
+            continue;
+        }
+        
+        // Images, only external URLs can be embedded
+        if let Some((alt, url)) = parse_image(line.trim())
+            && is_url(url)
+        {
             blocks.push(json!({
-                "type": "heading_3",
-                "heading_3": { "rich_text": [{ "text": { "content": &line[4..] } }] }
+                "type": "image",
+                "image": {
+                    "type": "external",
+                    "external": { "url": url },
+                    "caption": parse_inline(alt)
+                }
             }));
-        } 
-        // Bulleted list
-        else if line.starts_with("- ") || line.starts_with("* ") {
-            let content = &line[2..];
+            continue;
+        }
+        
+        // Dividers
+        if matches!(line.trim(), "---" | "***" | "___") {
             blocks.push(json!({
-                "type": "bulleted_list_item",
-                "bulleted_list_item": { "rich_text": [{ "text": { "content": content } }] }
+                "type": "divider",
+                "divider": {}
             }));
+            continue;
         }
-        // Numbered list
-        else if Regex::new(r"^\d+\.\s").unwrap().is_match(line) {
-            let content = Regex::new(r"^\d+\.\s").unwrap().replace(line, "");
+        
+        // Headers, Notion has three levels so H4-H6 become heading_3
+        if let Some((level, content)) = parse_heading(line) {
+            let heading_type = format!("heading_{}", level.min(3));
             blocks.push(json!({
-                "type": "numbered_list_item",
-                "numbered_list_item": { "rich_text": [{ "text": { "content": content } }] }
+                "type": heading_type,
+                heading_type: { "rich_text": parse_inline(content) }
             }));
-        }
+        } 
         // Regular paragraphs
         else {
             blocks.push(json!({
                 "type": "paragraph",
-                "paragraph": { "rich_text": [{ "text": { "content": line } }] }
+                "paragraph": { "rich_text": parse_inline(line) }
             }));
         }
     }
     
     // Close any remaining code block
-    if let Some(code_block) = current_code_block {
-        blocks.push(code_block);
+    if let Some((language, code_lines)) = current_code_block {
+        blocks.push(code_block(language, &code_lines.join("\n")));
     }
     
     blocks
 }
 
-fn get_valid_notion_language(language: &str) -> &str {
-    // List of languages supported by the Notion API
-    let valid_languages = [
-        "abap", "agda", "arduino", "assembly", "bash", "basic", "c", "c#", "c++", 
-        "clojure", "coffeescript", "css", "dart", "diff", "docker", "elixir", 
-        "elm", "erlang", "f#", "flow", "fortran", "go", "graphql", "groovy", 
-        "haskell", "html", "java", "javascript", "json", "julia", "kotlin", "latex", 
-        "less", "lisp", "lua", "makefile", "markdown", "matlab", "mermaid", 
-        "nix", "objective-c", "ocaml", "pascal", "perl", "php", "python", 
-        "r", "ruby", "rust", "scala", "scheme", "scss", "shell", "sql", 
-        "swift", "typescript", "vb.net", "verilog", "vhdl", "xml", "yaml"
-    ];
-    
-    // Normalize the language name
-    let normalized = language.trim().to_lowercase();
-    
-    if valid_languages.contains(&normalized.as_str()) {
-        for &valid in &valid_languages {
-            if valid == normalized {
-                return valid;
+/// Strip the backslash from a line starting with an escaped punctuation character
+fn strip_block_escape(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix('\\')?;
+    rest.starts_with(|c: char| c.is_ascii_punctuation()).then_some(rest)
+}
+
+/// Parse an ATX heading (`#` to `######` followed by a space) into its level and text
+fn parse_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let content = line[level..].strip_prefix(' ')?;
+    Some((level, content.trim()))
+}
+
+/// Parse a `caption: text` line following a closing code fence
+fn parse_code_caption(line: &str) -> Option<&str> {
+    let caption = line.trim().strip_prefix("caption:")?.trim();
+    (!caption.is_empty()).then_some(caption)
+}
+
+/// Build a Notion code block, keeping the body in as few rich_text objects as the size limit allows
+///
+/// The language comes from the fence, or is guessed from the body when the fence names none.
+fn code_block(fence_language: &str, body: &str) -> Value {
+    let language = match fence_language.trim() {
+        "" => guess_code_language(body),
+        named => get_valid_notion_language(named),
+    };
+    let rich_text: Vec<Value> = split_at_char_boundaries(body, MAX_BLOCK_SIZE)
+        .into_iter()
+        .map(|content| json!({
+            "type": "text",
+            "text": { "content": content }
+        }))
+        .collect();
+    json!({
+        "type": "code",
+        "code": {
+            "rich_text": rich_text,
+            "language": language
+        }
+    })
+}
+
+/// Whether a block is a spacer paragraph emitted for a blank line
+fn is_empty_paragraph(block: &Value) -> bool {
+    block["type"] == "paragraph" && block["paragraph"]["rich_text"].as_array().is_some_and(|t| t.is_empty())
+}
+
+/// Title of a `>>> Title` line opening a toggle
+fn parse_toggle_start(line: &str) -> Option<&str> {
+    line.trim().strip_prefix(">>>").map(str::trim)
+}
+
+/// Index of the `<<<` line closing a toggle whose content starts at `lines[0]`,
+/// skipping over nested toggles
+fn find_toggle_end(lines: &[&str]) -> Option<usize> {
+    let mut depth = 0;
+    for (index, line) in lines.iter().enumerate() {
+        if parse_toggle_start(line).is_some() {
+            depth += 1;
+        } else if line.trim() == "<<<" {
+            if depth == 0 {
+                return Some(index);
             }
+            depth -= 1;
         }
     }
-    
-    if normalized.is_empty() {
-        return "plain text";
+    None
+}
+
+/// Build a Notion equation block from a LaTeX expression
+fn equation_block(expression: &str) -> Value {
+    json!({
+        "type": "equation",
+        "equation": { "expression": expression }
+    })
+}
+
+/// Build the Notion block for a bulleted, numbered or to-do list line
+fn list_item_block(line: &str) -> Option<Value> {
+    if let Some((checked, content)) = parse_todo(line) {
+        Some(json!({
+            "type": "to_do",
+            "to_do": { "rich_text": parse_inline(content), "checked": checked }
+        }))
+    } else if let Some(content) = line.strip_prefix("- ").or_else(|| line.strip_prefix("* ")) {
+        Some(json!({
+            "type": "bulleted_list_item",
+            "bulleted_list_item": { "rich_text": parse_inline(content) }
+        }))
+    } else if NUMBERED_LIST_REGEX.is_match(line) {
+        let content = NUMBERED_LIST_REGEX.replace(line, "");
+        Some(json!({
+            "type": "numbered_list_item",
+            "numbered_list_item": { "rich_text": parse_inline(&content) }
+        }))
     } else {
-        // Try to find a close match
-        match normalized.as_str() {
-            "js" | "jsx" => "javascript",
-            "py" => "python",
-            "ts" | "tsx" => "typescript",
-            "sh" | "zsh" => "shell",
-            "md" => "markdown",
-            _ => "plain text"
+        None
+    }
+}
+
+/// Width of the leading whitespace of a line, counting a tab as four spaces
+fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+/// Remove up to `width` columns of leading whitespace, as measured by [`indent_width`]
+fn strip_indent(line: &str, width: usize) -> &str {
+    let mut removed = 0;
+    for (index, c) in line.char_indices() {
+        if removed >= width || !c.is_whitespace() {
+            return &line[index..];
         }
+        removed += if c == '\t' { 4 } else { 1 };
     }
+    ""
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    
-    #[test]
-    fn test_split_content_small_text() {
-        let text = "This is a small text.";
-        let chunks = split_content(text, 100);
-        assert_eq!(chunks.len(), 1);
-        assert_eq!(chunks[0], text);
+/// Offset of the closing fence of a code block whose body starts at `lines`,
+/// when a top-level numbered list item is the next non-blank line after it
+fn numbered_list_resumes_after_code(lines: &[&str]) -> Option<usize> {
+    let close = lines.iter().position(|l| l.trim() == "```")?;
+    let next = lines[close + 1..].iter().find(|l| !l.trim().is_empty())?;
+    NUMBERED_LIST_REGEX.is_match(next).then_some(close)
+}
+
+/// Push a block `depth` levels down the `children` of the last block
+fn push_nested(blocks: &mut Vec<Value>, block: Value, depth: usize) {
+    if depth == 0 {
+        blocks.push(block);
+        return;
     }
-    
-    #[test]
-    fn test_split_content_large_text_no_headers() {
-        let text = "A".repeat(3000); // Large text without headers
-        let chunks = split_content(&text, 1000);
-        assert!(chunks.len() > 1);
-        for chunk in chunks {
-            assert!(chunk.len() <= 1000);
-        }
+    let Some(parent) = blocks.last_mut() else {
+        blocks.push(block);
+        return;
+    };
+    let parent_type = parent["type"].as_str().unwrap_or("").to_string();
+    let children = &mut parent[parent_type.as_str()]["children"];
+    if !children.is_array() {
+        *children = json!([]);
     }
-    
-    #[test]
-    fn test_split_content_with_headers() {
-        let text = format!(
-            "# Title 1\n{}\n\n## Title 2\n{}\n\n### Title 3\n{}",
-            "A".repeat(900),
-            "B".repeat(900),
-            "C".repeat(900)
-        );
-        let chunks = split_content(&text, 1000);
-        assert!(chunks.len() > 1);
-        for chunk in chunks {
-            assert!(chunk.len() <= 1000);
+    push_nested(children.as_array_mut().unwrap(), block, depth - 1);
+}
+
+/// Split a `| a | b |` table row into its trimmed cells
+fn parse_table_row(line: &str) -> Option<Vec<&str>> {
+    let line = line.trim();
+    if !line.starts_with('|') || line.len() < 2 || !line.ends_with('|') {
+        return None;
+    }
+    Some(line[1..line.len() - 1].split('|').map(str::trim).collect())
+}
+
+/// Whether a line is the `|---|:---:|` row separating a table header from its body
+fn is_table_separator(line: &str) -> bool {
+    parse_table_row(line).is_some_and(|cells| {
+        cells.iter().all(|cell| {
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            !dashes.is_empty() && dashes.chars().all(|c| c == '-')
+        })
+    })
+}
+
+/// Build a Notion table block, padding short rows to the widest row
+fn table_block(rows: &[Vec<&str>]) -> Value {
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let children: Vec<Value> = rows
+        .iter()
+        .map(|row| {
+            let cells: Vec<Value> = (0..width)
+                .map(|col| Value::Array(parse_inline(row.get(col).copied().unwrap_or(""))))
+                .collect();
+            json!({
+                "type": "table_row",
+                "table_row": { "cells": cells }
+            })
+        })
+        .collect();
+    json!({
+        "type": "table",
+        "table": {
+            "table_width": width,
+            "has_column_header": true,
+            "has_row_header": false,
+            "children": children
         }
+    })
+}
+
+/// Parse a line consisting only of a Markdown image `![alt](url)`
+fn parse_image(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix("![")?.strip_suffix(')')?;
+    let (alt, url) = rest.split_once("](")?;
+    let url = url.trim();
+    if url.is_empty() || url.contains(char::is_whitespace) {
+        return None;
     }
-    
-    #[test]
-    fn test_format_for_notion_heading() {
-        let text = "# Main Title";
-        let blocks = format_for_notion(text);
+    Some((alt, url))
+}
+
+/// Rich text of a quote, with a trailing `— Author` attribution set in italics
+fn quote_rich_text(text: &str) -> Vec<Value> {
+    let Some((quote, attribution)) = split_quote_attribution(text) else {
+        return parse_inline(text);
+    };
+    let mut segments = parse_inline(quote);
+    let style = InlineStyle { italic: true, ..InlineStyle::default() };
+    parse_inline_into(&convert_inline_html(attribution, html_mode()), style, &mut segments);
+    segments
+}
+
+/// Split a quote ending in `— Author` into the quote, keeping the whitespace
+/// before the dash, and the attribution starting at the dash
+fn split_quote_attribution(text: &str) -> Option<(&str, &str)> {
+    let dash = text.rfind("— ")?;
+    let (quote, attribution) = text.split_at(dash);
+    let separated = quote.ends_with(char::is_whitespace);
+    let author = attribution["— ".len()..].trim();
+    if !separated || quote.trim().is_empty() || author.is_empty() || author.contains('\n') {
+        return None;
+    }
+    Some((quote, attribution.trim_end()))
+}
+
+/// Strip the `>` marker of a blockquote line, returning None for other lines
+fn strip_quote_marker(line: &str) -> Option<&str> {
+    if line == ">" {
+        return Some("");
+    }
+    line.strip_prefix("> ")
+}
+
+/// Admonition types and the emoji their callouts are shown with
+const CALLOUT_EMOJIS: [(&str, &str); 3] = [("NOTE", "ℹ️"), ("WARNING", "⚠️"), ("TIP", "💡")];
+
+/// Emoji for admonition types without their own entry in [`CALLOUT_EMOJIS`]
+const DEFAULT_CALLOUT_EMOJI: &str = "📌";
+
+/// Parse the `[!TYPE] optional title` first line of an admonition
+///
+/// Returns the upper-cased type and the rest of the line.
+fn parse_admonition(line: &str) -> Option<(String, &str)> {
+    let rest = line.trim_start().strip_prefix("[!")?;
+    let end = rest.find(']')?;
+    let kind = &rest[..end];
+    if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some((kind.to_ascii_uppercase(), rest[end + 1..].trim()))
+}
+
+fn callout_emoji(kind: &str) -> &'static str {
+    CALLOUT_EMOJIS
+        .iter()
+        .find(|(name, _)| *name == kind)
+        .map_or(DEFAULT_CALLOUT_EMOJI, |(_, emoji)| emoji)
+}
+
+/// Parse a task list line such as `- [ ] todo` or `- [x] done`
+///
+/// Returns the checked state and the item text without its prefix.
+fn parse_todo(line: &str) -> Option<(bool, &str)> {
+    let item = line.strip_prefix("- ").or_else(|| line.strip_prefix("* "))?;
+    let (checked, content) = if let Some(content) = item.strip_prefix("[ ]") {
+        (false, content)
+    } else if let Some(content) = item.strip_prefix("[x]").or_else(|| item.strip_prefix("[X]")) {
+        (true, content)
+    } else {
+        return None;
+    };
+    if !(content.is_empty() || content.starts_with(' ')) {
+        return None;
+    }
+    Some((checked, content.trim_start()))
+}
+
+/// Inline text styles that map onto Notion rich_text annotations and links
+#[derive(Debug, Clone, Copy, Default)]
+struct InlineStyle<'a> {
+    bold: bool,
+    italic: bool,
+    strikethrough: bool,
+    code: bool,
+    link: Option<&'a str>,
+}
+
+/// Emphasis delimiters, longest first so `**` wins over `*`
+const EMPHASIS_DELIMITERS: [&str; 4] = ["**", "~~", "*", "_"];
+
+/// Convert a single line of Markdown into Notion rich_text objects
+///
+/// Recognizes `**bold**`, `*italic*` / `_italic_`, `~~strikethrough~~`,
+/// `` `code` `` spans, `[text](url)` links and `<url>` autolinks. Delimiters
+/// without a matching closer and bare URLs are kept as literal text. Inline
+/// HTML tags are handled as configured by [`html_mode`].
+///
+/// # Arguments
+///
+/// * `text` - Line of Markdown text
+///
+/// # Returns
+///
+/// * `Vec<Value>` - List of rich_text objects
+pub fn parse_inline(text: &str) -> Vec<Value> {
+    let text = convert_inline_html(text, html_mode());
+    let mut segments = Vec::new();
+    parse_inline_into(&text, InlineStyle::default(), &mut segments);
+    segments
+}
+
+/// How inline HTML tags in Markdown text are converted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HtmlMode {
+    /// Drop the tags and keep their text, `<br>` becomes a line break
+    Strip,
+    /// Like `Strip`, but bold, italic, strikethrough and code tags become annotations
+    Map,
+    /// Leave the tags as literal text
+    Keep,
+}
+
+/// HTML handling from `JOTDOWN_HTML` (`strip`, `map` or `keep`), stripping by default
+pub fn html_mode() -> HtmlMode {
+    html_mode_from(std::env::var("JOTDOWN_HTML").ok())
+}
+
+fn html_mode_from(value: Option<String>) -> HtmlMode {
+    match value.map(|v| v.trim().to_lowercase()).as_deref() {
+        Some("map") => HtmlMode::Map,
+        Some("keep") => HtmlMode::Keep,
+        _ => HtmlMode::Strip,
+    }
+}
+
+/// An opening, closing or self-closing HTML tag, compiled once
+static HTML_TAG_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"</?([A-Za-z][A-Za-z0-9]*)(?:\s[^<>]*)?/?>"#).unwrap());
+
+/// HTML elements recognized as tags, so text such as `Vec<String>` is left alone
+const HTML_ELEMENTS: &[&str] = &[
+    "a", "abbr", "b", "big", "br", "center", "cite", "code", "del", "dfn", "div", "em", "font",
+    "i", "img", "ins", "kbd", "mark", "p", "q", "s", "samp", "small", "span", "strike", "strong",
+    "sub", "sup", "tt", "u", "var", "wbr",
+];
+
+/// Convert the inline HTML tags of a line of Markdown, leaving code spans untouched
+fn convert_inline_html(text: &str, mode: HtmlMode) -> String {
+    if mode == HtmlMode::Keep || !text.contains('<') {
+        return text.to_string();
+    }
+    let mut converted = String::with_capacity(text.len());
+    // Odd pieces are inside `code` spans
+    for (index, piece) in text.split('`').enumerate() {
+        if index > 0 {
+            converted.push('`');
+        }
+        if index % 2 == 1 {
+            converted.push_str(piece);
+            continue;
+        }
+        converted.push_str(&HTML_TAG_REGEX.replace_all(piece, |captures: &regex::Captures| {
+            let name = captures[1].to_lowercase();
+            if !HTML_ELEMENTS.contains(&name.as_str()) {
+                return captures[0].to_string();
+            }
+            if name == "br" {
+                return "\n".to_string();
+            }
+            let markdown = match name.as_str() {
+                "b" | "strong" => "**",
+                "i" | "em" => "*",
+                "s" | "del" | "strike" => "~~",
+                "code" => "`",
+                _ => "",
+            };
+            if mode == HtmlMode::Map { markdown.to_string() } else { String::new() }
+        }));
+    }
+    converted
+}
+
+fn parse_inline_into<'a>(text: &'a str, style: InlineStyle<'a>, segments: &mut Vec<Value>) {
+    let mut plain = String::new();
+    let mut i = 0;
+
+    while i < text.len() {
+        let rest = &text[i..];
+
+        // Code spans are literal, nothing inside them is parsed
+        if rest.starts_with('`')
+            && let Some(end) = rest[1..].find('`')
+            && end > 0
+        {
+            push_text_segment(segments, &plain, style);
+            plain.clear();
+            push_text_segment(segments, &rest[1..=end], InlineStyle { code: true, ..style });
+            i += end + 2;
+            continue;
+        }
+
+        // Links: [text](url), the label may itself carry inline styles
+        if rest.starts_with('[')
+            && let Some((label_end, url_end)) = find_link(rest)
+        {
+            push_text_segment(segments, &plain, style);
+            plain.clear();
+            let link_style = InlineStyle { link: Some(&rest[label_end + 2..url_end]), ..style };
+            parse_inline_into(&rest[1..label_end], link_style, segments);
+            i += url_end + 1;
+            continue;
+        }
+
+        // Autolinks: <https://example.com>
+        if rest.starts_with('<')
+            && let Some(end) = rest.find('>')
+            && is_url(&rest[1..end])
+        {
+            push_text_segment(segments, &plain, style);
+            plain.clear();
+            let url = &rest[1..end];
+            push_text_segment(segments, url, InlineStyle { link: Some(url), ..style });
+            i += end + 1;
+            continue;
+        }
+
+        // Inline equations: $expression$, the expression is literal LaTeX
+        if rest.starts_with('$')
+            && let Some(end) = find_inline_equation(rest)
+        {
+            push_text_segment(segments, &plain, style);
+            plain.clear();
+            push_equation_segment(segments, &rest[1..end], style);
+            i += end + 1;
+            continue;
+        }
+
+        if let Some(delimiter) = EMPHASIS_DELIMITERS.iter().find(|d| rest.starts_with(**d))
+            && let Some(end) = find_closing_delimiter(text, i, delimiter)
+        {
+            push_text_segment(segments, &plain, style);
+            plain.clear();
+            let inner_style = match *delimiter {
+                "**" => InlineStyle { bold: true, ..style },
+                "~~" => InlineStyle { strikethrough: true, ..style },
+                _ => InlineStyle { italic: true, ..style },
+            };
+            parse_inline_into(&text[i + delimiter.len()..end], inner_style, segments);
+            i = end + delimiter.len();
+            continue;
+        }
+
+        let c = rest.chars().next().unwrap();
+        plain.push(c);
+        i += c.len_utf8();
+    }
+
+    push_text_segment(segments, &plain, style);
+}
+
+/// Find the byte offset of the delimiter closing the one opened at `start`
+fn find_closing_delimiter(text: &str, start: usize, delimiter: &str) -> Option<usize> {
+    let is_word_char = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric());
+
+    // `_` inside words (snake_case) is not emphasis
+    if delimiter == "_" && is_word_char(text[..start].chars().next_back()) {
+        return None;
+    }
+
+    let inner_start = start + delimiter.len();
+    if text[inner_start..].starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let mut search = inner_start;
+    while let Some(offset) = text[search..].find(delimiter) {
+        let end = search + offset;
+        let after = &text[end + delimiter.len()..];
+
+        // Skip a doubled single-char delimiter such as the `**` inside `*a **b** c*`
+        if delimiter.len() == 1 && after.starts_with(delimiter) {
+            search = end + 2;
+            continue;
+        }
+
+        let valid = end > inner_start
+            && !text[..end].ends_with(char::is_whitespace)
+            && !(delimiter == "_" && is_word_char(after.chars().next()));
+        if valid {
+            return Some(end);
+        }
+        search = end + delimiter.len();
+    }
+
+    None
+}
+
+/// Find the byte offset of the `$` closing an inline equation opened at the start of `text`
+///
+/// Like pandoc, the expression may not start or end with whitespace and the
+/// closing `$` may not be followed by a digit, so prices such as `$5 and $10`
+/// stay plain text.
+fn find_inline_equation(text: &str) -> Option<usize> {
+    if text.starts_with("$$") {
+        return None;
+    }
+    let end = text[1..].find('$')? + 1;
+    let expression = &text[1..end];
+    let valid = !expression.is_empty()
+        && !expression.starts_with(char::is_whitespace)
+        && !expression.ends_with(char::is_whitespace)
+        && !text[end + 1..].starts_with(|c: char| c.is_ascii_digit());
+    valid.then_some(end)
+}
+
+/// Locate a `[label](url)` link at the start of `text`
+///
+/// Returns the byte offsets of the closing `]` and the closing `)`.
+fn find_link(text: &str) -> Option<(usize, usize)> {
+    let label_end = text.find(']')?;
+    if label_end == 1 || !text[label_end + 1..].starts_with('(') {
+        return None;
+    }
+    let url_start = label_end + 2;
+    let url_end = url_start + text[url_start..].find(')')?;
+    // Notion only accepts absolute URLs, relative links stay literal text
+    if !is_url(&text[url_start..url_end]) {
+        return None;
+    }
+    Some((label_end, url_end))
+}
+
+fn is_url(text: &str) -> bool {
+    ["http://", "https://", "mailto:"].iter().any(|scheme| text.starts_with(scheme))
+        && !text.contains(char::is_whitespace)
+}
+
+/// Push styled text, split into as many segments as Notion's per-segment size limit needs
+fn push_text_segment(segments: &mut Vec<Value>, content: &str, style: InlineStyle) {
+    for piece in split_at_char_boundaries(content, MAX_BLOCK_SIZE) {
+        let text = match style.link {
+            Some(url) => json!({ "content": piece, "link": { "url": url } }),
+            None => json!({ "content": piece }),
+        };
+        segments.push(json!({
+            "type": "text",
+            "text": text,
+            "annotations": {
+                "bold": style.bold,
+                "italic": style.italic,
+                "strikethrough": style.strikethrough,
+                "code": style.code
+            }
+        }));
+    }
+}
+
+fn push_equation_segment(segments: &mut Vec<Value>, expression: &str, style: InlineStyle) {
+    segments.push(json!({
+        "type": "equation",
+        "equation": { "expression": expression },
+        "annotations": {
+            "bold": style.bold,
+            "italic": style.italic,
+            "strikethrough": style.strikethrough,
+            "code": style.code
+        }
+    }));
+}
+
+/// Guess the language of an unlabelled code block from telltale lines,
+/// falling back to plain text
+fn guess_code_language(body: &str) -> &'static str {
+    let trimmed = body.trim();
+    let lines: Vec<&str> = trimmed.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    let Some(first) = lines.first() else {
+        return "plain text";
+    };
+    if let Some(interpreter) = first.strip_prefix("#!") {
+        return if interpreter.contains("python") {
+            "python"
+        } else if interpreter.contains("node") {
+            "javascript"
+        } else {
+            "shell"
+        };
+    }
+    let bracketed = (trimmed.starts_with('{') && trimmed.ends_with('}'))
+        || (trimmed.starts_with('[') && trimmed.ends_with(']'));
+    if bracketed && (trimmed.contains("\":") || serde_json::from_str::<Value>(trimmed).is_ok()) {
+        return "json";
+    }
+    if lines.iter().any(|l| l.starts_with("$ ")) {
+        return "shell";
+    }
+    const RUST_STARTS: [&str; 6] = ["fn ", "pub fn ", "let ", "use ", "impl ", "#[derive("];
+    if lines.iter().any(|l| RUST_STARTS.iter().any(|start| l.starts_with(start))) {
+        return "rust";
+    }
+    "plain text"
+}
+
+fn get_valid_notion_language(language: &str) -> &str {
+    // List of languages supported by the Notion API
+    let valid_languages = [
+        "abap", "agda", "arduino", "assembly", "bash", "basic", "c", "c#", "c++", 
+        "clojure", "coffeescript", "css", "dart", "diff", "docker", "elixir", 
+        "elm", "erlang", "f#", "flow", "fortran", "go", "graphql", "groovy", 
+        "haskell", "html", "java", "javascript", "json", "julia", "kotlin", "latex", 
+        "less", "lisp", "lua", "makefile", "markdown", "matlab", "mermaid", 
+        "nix", "objective-c", "ocaml", "pascal", "perl", "php", "python", 
+        "r", "ruby", "rust", "scala", "scheme", "scss", "shell", "sql", 
+        "swift", "typescript", "vb.net", "verilog", "vhdl", "xml", "yaml"
+    ];
+    
+    // Normalize the language name
+    let normalized = language.trim().to_lowercase();
+    
+    if valid_languages.contains(&normalized.as_str()) {
+        for &valid in &valid_languages {
+            if valid == normalized {
+                return valid;
+            }
+        }
+    }
+    
+    if normalized.is_empty() {
+        "plain text"
+    } else {
+        // Try to find a close match
+        match normalized.as_str() {
+            "js" | "jsx" => "javascript",
+            "py" => "python",
+            "ts" | "tsx" => "typescript",
+            "sh" | "zsh" => "shell",
+            "md" => "markdown",
+            _ => "plain text"
+        }
+    }
+}
+
+/// Convert Notion blocks back into Markdown text
+///
+/// This is the reverse of [`format_for_notion`]: headings, lists, code
+/// blocks and paragraphs are rendered with their Markdown syntax and
+/// rich_text annotations become inline Markdown markers.
+///
+/// # Arguments
+///
+/// * `blocks` - List of Notion blocks as returned by the API
+///
+/// # Returns
+///
+/// * `String` - Markdown text
+pub fn blocks_to_markdown(blocks: &[Value]) -> String {
+    let mut lines = Vec::new();
+    render_markdown_lines(blocks, "", &mut lines);
+    lines.join("\n")
+}
+
+/// Build a short plain-text summary of blocks for database previews
+///
+/// Text from every block (including nested children) is joined with single
+/// spaces, so Markdown markers are already gone, and cut at `max_chars`
+/// characters with an ellipsis.
+///
+/// # Arguments
+///
+/// * `blocks` - List of Notion blocks
+/// * `max_chars` - Maximum length of the summary in characters
+///
+/// # Returns
+///
+/// * `String` - Plain-text summary, empty if the blocks hold no text
+pub fn summarize_blocks(blocks: &[Value], max_chars: usize) -> String {
+    let text = blocks_to_plain_text(blocks)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    match text.char_indices().nth(max_chars) {
+        Some((cut, _)) => format!("{}…", text[..cut].trim_end()),
+        None => text,
+    }
+}
+
+/// Reconstruct the plain text of blocks, one line per block
+///
+/// Nested children follow their parent; annotations and Markdown syntax are
+/// dropped.
+///
+/// # Arguments
+///
+/// * `blocks` - List of Notion blocks
+///
+/// # Returns
+///
+/// * `String` - Plain text of the blocks
+pub fn blocks_to_plain_text(blocks: &[Value]) -> String {
+    let mut lines = Vec::new();
+    collect_block_text(blocks, &mut lines);
+    lines.join("\n")
+}
+
+/// Count blocks including all nested children
+pub fn count_blocks(blocks: &[Value]) -> usize {
+    blocks
+        .iter()
+        .map(|block| {
+            let block_type = block.get("type").and_then(|v| v.as_str()).unwrap_or("");
+            let children = block[block_type]["children"].as_array().map(|v| v.as_slice()).unwrap_or(&[]);
+            1 + count_blocks(children)
+        })
+        .sum()
+}
+
+fn collect_block_text(blocks: &[Value], lines: &mut Vec<String>) {
+    for block in blocks {
+        let block_type = block.get("type").and_then(|v| v.as_str()).unwrap_or("");
+        let rich_text = block[block_type]["rich_text"].as_array().map(|v| v.as_slice()).unwrap_or(&[]);
+        lines.push(rich_text_plain(rich_text));
+        if let Some(children) = block[block_type]["children"].as_array() {
+            collect_block_text(children, lines);
+        }
+    }
+}
+
+fn render_markdown_lines(blocks: &[Value], indent: &str, lines: &mut Vec<String>) {
+    let mut list_number = 0;
+
+    for block in blocks {
+        let block_type = block.get("type").and_then(|v| v.as_str()).unwrap_or("");
+        let rich_text = block
+            .get(block_type)
+            .and_then(|v| v.get("rich_text"))
+            .and_then(|v| v.as_array())
+            .map(|v| v.as_slice())
+            .unwrap_or(&[]);
+
+        if block_type == "numbered_list_item" {
+            list_number += 1;
+        } else {
+            list_number = 0;
+        }
+
+        let line = match block_type {
+            "heading_1" => format!("# {}", rich_text_to_markdown(rich_text)),
+            "heading_2" => format!("## {}", rich_text_to_markdown(rich_text)),
+            "heading_3" => format!("### {}", rich_text_to_markdown(rich_text)),
+            "bulleted_list_item" => format!("- {}", rich_text_to_markdown(rich_text)),
+            "numbered_list_item" => format!("{}. {}", list_number, rich_text_to_markdown(rich_text)),
+            "quote" => rich_text_to_markdown(rich_text)
+                .split('\n')
+                .map(|l| if l.is_empty() { ">".to_string() } else { format!("> {}", l) })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            "divider" => "---".to_string(),
+            "callout" => {
+                let emoji = block["callout"]["icon"]["emoji"].as_str().unwrap_or("");
+                let kind = CALLOUT_EMOJIS
+                    .iter()
+                    .find(|(_, e)| *e == emoji)
+                    .map_or("NOTE", |(name, _)| name);
+                std::iter::once(format!("> [!{}]", kind))
+                    .chain(rich_text_to_markdown(rich_text).split('\n').map(|l| {
+                        if l.is_empty() { ">".to_string() } else { format!("> {}", l) }
+                    }))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            "toggle" => {
+                let mut toggle = vec![format!(">>> {}", rich_text_to_markdown(rich_text))];
+                if let Some(children) = block["toggle"]["children"].as_array() {
+                    render_markdown_lines(children, "", &mut toggle);
+                }
+                toggle.push("<<<".to_string());
+                toggle.join("\n")
+            }
+            "equation" => format!("$$\n{}\n$$", block["equation"]["expression"].as_str().unwrap_or("")),
+            "table" => {
+                let rows: Vec<String> = block["table"]["children"]
+                    .as_array()
+                    .map(|v| v.as_slice())
+                    .unwrap_or(&[])
+                    .iter()
+                    .map(|row| {
+                        let cells: Vec<String> = row["table_row"]["cells"]
+                            .as_array()
+                            .map(|v| v.as_slice())
+                            .unwrap_or(&[])
+                            .iter()
+                            .map(|cell| rich_text_to_markdown(cell.as_array().map(|v| v.as_slice()).unwrap_or(&[])))
+                            .collect();
+                        format!("| {} |", cells.join(" | "))
+                    })
+                    .collect();
+                let width = block["table"]["table_width"].as_u64().unwrap_or(0) as usize;
+                let separator = format!("|{}", "---|".repeat(width));
+                let mut table = rows;
+                if !table.is_empty() {
+                    table.insert(1, separator);
+                }
+                table.join("\n")
+            }
+            "image" => {
+                let image = &block["image"];
+                let url = image["external"]["url"]
+                    .as_str()
+                    .or_else(|| image["file"]["url"].as_str())
+                    .unwrap_or("");
+                let caption = image["caption"].as_array().map(|v| v.as_slice()).unwrap_or(&[]);
+                format!("![{}]({})", rich_text_plain(caption), url)
+            }
+            "to_do" => {
+                let checked = block["to_do"]["checked"].as_bool().unwrap_or(false);
+                format!("- [{}] {}", if checked { "x" } else { " " }, rich_text_to_markdown(rich_text))
+            }
+            "code" => {
+                let language = block["code"]["language"].as_str().unwrap_or("plain text");
+                let language = if language == "plain text" { "" } else { language };
+                let body = rich_text_plain(rich_text);
+                let mut code = format!("```{}\n{}\n```", language, body.trim_end_matches('\n'));
+                let caption = block["code"]["caption"].as_array().map(|v| v.as_slice()).unwrap_or(&[]);
+                if !caption.is_empty() {
+                    code.push_str(&format!("\ncaption: {}", rich_text_to_markdown(caption)));
+                }
+                code
+            }
+            _ => rich_text_to_markdown(rich_text),
+        };
+        lines.extend(line.split('\n').map(|l| format!("{}{}", indent, l)));
+
+        // Table rows and toggle content were rendered with the block itself
+        if block_type != "table"
+            && block_type != "toggle"
+            && let Some(children) = block[block_type]["children"].as_array()
+        {
+            render_markdown_lines(children, &format!("{}{}", indent, LIST_INDENT), lines);
+        }
+    }
+}
+
+/// Concatenate the plain text of rich_text objects, ignoring annotations
+fn rich_text_plain(rich_text: &[Value]) -> String {
+    rich_text
+        .iter()
+        .map(|segment| {
+            segment
+                .get("plain_text")
+                .or_else(|| segment.get("text").and_then(|v| v.get("content")))
+                .or_else(|| segment.get("equation").and_then(|v| v.get("expression")))
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+        })
+        .collect()
+}
+
+/// Render rich_text objects as inline Markdown
+fn rich_text_to_markdown(rich_text: &[Value]) -> String {
+    let mut markdown = String::new();
+    for segment in rich_text {
+        let mut text = rich_text_plain(std::slice::from_ref(segment));
+        if text.is_empty() {
+            continue;
+        }
+        if segment["type"] == "equation" {
+            text = format!("${}$", segment["equation"]["expression"].as_str().unwrap_or(&text));
+        }
+        let annotations = &segment["annotations"];
+        if annotations["code"].as_bool().unwrap_or(false) {
+            text = format!("`{}`", text);
+        }
+        if annotations["bold"].as_bool().unwrap_or(false) {
+            text = format!("**{}**", text);
+        }
+        if annotations["italic"].as_bool().unwrap_or(false) {
+            text = format!("*{}*", text);
+        }
+        if annotations["strikethrough"].as_bool().unwrap_or(false) {
+            text = format!("~~{}~~", text);
+        }
+        let link = segment["text"]["link"]["url"].as_str().or_else(|| segment["href"].as_str());
+        if let Some(url) = link {
+            text = format!("[{}]({})", text, url);
+        }
+        markdown.push_str(&text);
+    }
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_split_content_small_text() {
+        let text = "This is a small text.";
+        let chunks = split_content(text, 100);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0], text);
+    }
+    
+    #[test]
+    fn test_split_content_large_text_no_headers() {
+        let text = "A".repeat(3000); // Large text without headers
+        let chunks = split_content(&text, 1000);
+        assert!(chunks.len() > 1);
+        for chunk in chunks {
+            assert!(chunk.len() <= 1000);
+        }
+    }
+    
+    #[test]
+    fn test_split_content_with_headers() {
+        let text = format!(
+            "# Title 1\n{}\n\n## Title 2\n{}\n\n### Title 3\n{}",
+            "A".repeat(900),
+            "B".repeat(900),
+            "C".repeat(900)
+        );
+        let chunks = split_content(&text, 1000);
+        assert!(chunks.len() > 1);
+        for chunk in chunks {
+            assert!(chunk.len() <= 1000);
+        }
+    }
+    
+    #[test]
+    fn test_split_content_keeps_text_before_first_header() {
+        let text = format!("intro text\n\n# Header\nbody {}", "x".repeat(60));
+        let chunks = split_content(&text, 50);
+        assert_eq!(chunks[0], "intro text\n\n");
+        assert!(chunks[1].starts_with("# Header"));
+    }
+    
+    #[test]
+    fn test_split_content_with_headers_has_no_duplicates() {
+        let text = format!("# A\n{}\n# B\n{}\n# C\n{}", "a".repeat(40), "b".repeat(40), "c".repeat(40));
+        let chunks = split_content(&text, 100);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks.concat(), text);
+    }
+    
+    #[test]
+    fn test_split_content_multibyte_chars() {
+        let text = "🥬".repeat(750); // 3000 bytes, 4 bytes per char
+        let chunks = split_content(&text, 1000);
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.len() <= 1000);
+        }
+        assert_eq!(chunks.concat(), text);
+    }
+    
+    #[test]
+    fn test_split_content_multibyte_mixed_text() {
+        let text = format!("# Résumé\n{}\n## Été\n{}", "é".repeat(700), "ü".repeat(700));
+        let chunks = split_content(&text, 1000);
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.len() <= 1000);
+        }
+    }
+    
+    #[test]
+    fn test_format_for_notion_heading() {
+        let text = "# Main Title";
+        let blocks = format_for_notion(text);
         assert_eq!(blocks.len(), 1);
         assert_eq!(blocks[0]["type"], "heading_1");
         assert_eq!(blocks[0]["heading_1"]["rich_text"][0]["text"]["content"], "Main Title");
@@ -401,4 +1689,602 @@ mod tests {
         assert_eq!(blocks[0]["type"], "paragraph");
         assert_eq!(blocks[0]["paragraph"]["rich_text"][0]["text"]["content"], text);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_parse_inline_bold_and_code() {
+        let segments = parse_inline("This is **bold** and `code`");
+        assert_eq!(segments.len(), 4);
+        assert_eq!(segments[0]["text"]["content"], "This is ");
+        assert_eq!(segments[0]["annotations"]["bold"], false);
+        assert_eq!(segments[1]["text"]["content"], "bold");
+        assert_eq!(segments[1]["annotations"]["bold"], true);
+        assert_eq!(segments[1]["annotations"]["code"], false);
+        assert_eq!(segments[2]["text"]["content"], " and ");
+        assert_eq!(segments[3]["text"]["content"], "code");
+        assert_eq!(segments[3]["annotations"]["code"], true);
+        assert_eq!(segments[3]["annotations"]["bold"], false);
+    }
+    
+    #[test]
+    fn test_parse_inline_italic_and_strikethrough() {
+        let segments = parse_inline("*one* _two_ ~~three~~");
+        assert_eq!(segments[0]["text"]["content"], "one");
+        assert_eq!(segments[0]["annotations"]["italic"], true);
+        assert_eq!(segments[2]["text"]["content"], "two");
+        assert_eq!(segments[2]["annotations"]["italic"], true);
+        assert_eq!(segments[4]["text"]["content"], "three");
+        assert_eq!(segments[4]["annotations"]["strikethrough"], true);
+    }
+    
+    #[test]
+    fn test_parse_inline_leaves_unmatched_markers() {
+        let segments = parse_inline("2 * 3 = 6 and snake_case_name");
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0]["text"]["content"], "2 * 3 = 6 and snake_case_name");
+    }
+    
+    #[test]
+    fn test_parse_inline_link() {
+        let segments = parse_inline("See [docs](https://example.com) now");
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0]["text"]["content"], "See ");
+        assert!(segments[0]["text"]["link"].is_null());
+        assert_eq!(segments[1]["text"]["content"], "docs");
+        assert_eq!(segments[1]["text"]["link"]["url"], "https://example.com");
+        assert_eq!(segments[2]["text"]["content"], " now");
+        assert!(segments[2]["text"]["link"].is_null());
+    }
+    
+    #[test]
+    fn test_parse_inline_multiple_links_and_autolink() {
+        let segments = parse_inline("[a](https://a.io) and [**b**](https://b.io) <https://c.io>");
+        assert_eq!(segments[0]["text"]["link"]["url"], "https://a.io");
+        assert_eq!(segments[2]["text"]["content"], "b");
+        assert_eq!(segments[2]["text"]["link"]["url"], "https://b.io");
+        assert_eq!(segments[2]["annotations"]["bold"], true);
+        assert_eq!(segments[4]["text"]["content"], "https://c.io");
+        assert_eq!(segments[4]["text"]["link"]["url"], "https://c.io");
+    }
+    
+    #[test]
+    fn test_parse_inline_bare_url_untouched() {
+        let segments = parse_inline("Visit https://example.com today");
+        assert_eq!(segments.len(), 1);
+        assert!(segments[0]["text"]["link"].is_null());
+    }
+    
+    #[test]
+    fn test_format_for_notion_inline_styles_in_list_item() {
+        let blocks = format_for_notion("- a **bold** item");
+        let rich_text = &blocks[0]["bulleted_list_item"]["rich_text"];
+        assert_eq!(rich_text[1]["text"]["content"], "bold");
+        assert_eq!(rich_text[1]["annotations"]["bold"], true);
+    }
+    
+    #[test]
+    fn test_blocks_to_markdown_round_trip() {
+        let markdown = "# Title\n\nSome **bold** and [link](https://example.com)\n> quoted\n> lines\n---\n- [ ] todo\n- [x] done\n- one\n  - nested\n    1. deeper\n- two\n1. first\n2. second\n```rust\nfn main() {}\n```";
+        let blocks = format_for_notion(markdown);
+        assert_eq!(blocks_to_markdown(&blocks), markdown);
+    }
+    
+    #[test]
+    fn test_blocks_to_markdown_uses_plain_text_from_api() {
+        let blocks = vec![json!({
+            "type": "paragraph",
+            "paragraph": {
+                "rich_text": [{
+                    "type": "text",
+                    "text": { "content": "hello", "link": null },
+                    "annotations": { "bold": false, "italic": true, "strikethrough": false, "code": false },
+                    "plain_text": "hello",
+                    "href": null
+                }]
+            }
+        })];
+        assert_eq!(blocks_to_markdown(&blocks), "*hello*");
+    }
+    
+    #[test]
+    fn test_format_for_notion_quote_attribution() {
+        let blocks = format_for_notion("> Be yourself; everyone else is already taken.\n> — Oscar Wilde");
+        let rich_text = blocks[0]["quote"]["rich_text"].as_array().unwrap();
+        assert_eq!(rich_text.len(), 2);
+        assert_eq!(rich_text[0]["text"]["content"], "Be yourself; everyone else is already taken.\n");
+        assert_eq!(rich_text[0]["annotations"]["italic"], false);
+        assert_eq!(rich_text[1]["text"]["content"], "— Oscar Wilde");
+        assert_eq!(rich_text[1]["annotations"]["italic"], true);
+
+        let blocks = format_for_notion("> Stay hungry — Steve Jobs");
+        let rich_text = blocks[0]["quote"]["rich_text"].as_array().unwrap();
+        assert_eq!(rich_text[0]["text"]["content"], "Stay hungry ");
+        assert_eq!(rich_text[1]["text"]["content"], "— Steve Jobs");
+        assert_eq!(rich_text[1]["annotations"]["italic"], true);
+    }
+
+    #[test]
+    fn test_format_for_notion_plain_quote_keeps_dashes() {
+        for quote in ["> a pause—then more", "> — just a dash", "> before — middle\n> after"] {
+            let blocks = format_for_notion(quote);
+            let rich_text = blocks[0]["quote"]["rich_text"].as_array().unwrap();
+            assert_eq!(rich_text.len(), 1, "{}", quote);
+            assert_eq!(rich_text[0]["annotations"]["italic"], false);
+        }
+    }
+
+    #[test]
+    fn test_format_for_notion_detects_json_code() {
+        let blocks = format_for_notion("```\n{\n  \"name\": \"jotdown\",\n  \"tags\": [\"a\"]\n}\n```");
+        assert_eq!(blocks[0]["code"]["language"], "json");
+        let blocks = format_for_notion("```\n[1, 2, 3]\n```");
+        assert_eq!(blocks[0]["code"]["language"], "json");
+    }
+
+    #[test]
+    fn test_format_for_notion_detects_shell_code() {
+        let blocks = format_for_notion("```\n$ cargo build --release\n$ ./target/release/Jotdown\n```");
+        assert_eq!(blocks[0]["code"]["language"], "shell");
+        let blocks = format_for_notion("```\n#!/bin/bash\necho hi\n```");
+        assert_eq!(blocks[0]["code"]["language"], "shell");
+    }
+
+    #[test]
+    fn test_guess_code_language_falls_back_to_plain_text() {
+        assert_eq!(guess_code_language("fn main() {\n    let x = 1;\n}"), "rust");
+        assert_eq!(guess_code_language("#!/usr/bin/env python3\nprint(1)"), "python");
+        assert_eq!(guess_code_language("just some words"), "plain text");
+        assert_eq!(guess_code_language("{ not json }"), "plain text");
+        assert_eq!(guess_code_language(""), "plain text");
+    }
+
+    #[test]
+    fn test_format_for_notion_explicit_language_wins() {
+        let blocks = format_for_notion("```text\n$ not detected\n```\n```yaml\n{\"a\": 1}\n```");
+        assert_eq!(blocks[0]["code"]["language"], "plain text");
+        assert_eq!(blocks[1]["code"]["language"], "yaml");
+    }
+
+    #[test]
+    fn test_format_for_notion_crlf_line_endings() {
+        let blocks = format_for_notion("# Title\r\n\r\nSome **bold** text\r\n- item\r\n```rust\r\nfn main() {}\r\n```\r\nold\rmac");
+        let types: Vec<&str> = blocks.iter().map(|b| b["type"].as_str().unwrap()).collect();
+        assert_eq!(types, vec!["heading_1", "paragraph", "paragraph", "bulleted_list_item", "code", "paragraph", "paragraph"]);
+        for block in &blocks {
+            let block_type = block["type"].as_str().unwrap();
+            for segment in block[block_type]["rich_text"].as_array().unwrap() {
+                assert!(!segment["text"]["content"].as_str().unwrap().contains('\r'), "{}", block);
+            }
+        }
+        assert_eq!(blocks[0]["heading_1"]["rich_text"][0]["text"]["content"], "Title");
+        assert_eq!(blocks[4]["code"]["language"], "rust");
+    }
+
+    #[test]
+    fn test_split_content_crlf_line_endings() {
+        let chunks = split_content("# One\r\nfirst\r\n# Two\r\nsecond", 12);
+        assert!(chunks.iter().all(|chunk| !chunk.contains('\r')));
+        assert_eq!(chunks.concat(), "# One\nfirst\n# Two\nsecond");
+    }
+
+    #[test]
+    fn test_split_footnotes_numbers_by_first_reference() {
+        let text = "A[^b] and B[^a], again[^b], missing[^x] and `code[^a]`\n[^a]: First\n[^b]: Second\n    continued\n[^c]: Unused";
+        let (body, notes) = split_footnotes(text);
+        assert_eq!(body, "A[1] and B[2], again[1], missing[^x] and `code[^a]`");
+        let notes: Vec<&str> = notes[1..]
+            .iter()
+            .map(|b| b["numbered_list_item"]["rich_text"][0]["text"]["content"].as_str().unwrap())
+            .collect();
+        assert_eq!(notes, vec!["Second continued", "First", "Unused"]);
+    }
+
+    #[test]
+    fn test_split_footnotes_leaves_unmatched_references() {
+        let text = "See[^1]\n```\n[^1]: not a definition\n```";
+        let (body, notes) = split_footnotes(text);
+        assert_eq!(body, text);
+        assert!(notes.is_empty());
+    }
+
+    #[test]
+    fn test_format_for_notion_multiline_quote() {
+        let blocks = format_for_notion("> first line\n> second line\nafter");
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0]["type"], "quote");
+        assert_eq!(blocks[0]["quote"]["rich_text"][0]["text"]["content"], "first line\nsecond line");
+        assert_eq!(blocks[1]["type"], "paragraph");
+    }
+    
+    #[test]
+    fn test_format_for_notion_divider() {
+        for marker in ["---", "***", "___"] {
+            let blocks = format_for_notion(marker);
+            assert_eq!(blocks.len(), 1);
+            assert_eq!(blocks[0]["type"], "divider");
+        }
+    }
+    
+    #[test]
+    fn test_format_for_notion_unchecked_todo() {
+        let blocks = format_for_notion("- [ ] buy milk");
+        assert_eq!(blocks[0]["type"], "to_do");
+        assert_eq!(blocks[0]["to_do"]["checked"], false);
+        assert_eq!(blocks[0]["to_do"]["rich_text"][0]["text"]["content"], "buy milk");
+    }
+    
+    #[test]
+    fn test_format_for_notion_checked_todo() {
+        for line in ["- [x] ship it", "* [X] ship it"] {
+            let blocks = format_for_notion(line);
+            assert_eq!(blocks[0]["type"], "to_do");
+            assert_eq!(blocks[0]["to_do"]["checked"], true);
+            assert_eq!(blocks[0]["to_do"]["rich_text"][0]["text"]["content"], "ship it");
+        }
+    }
+    
+    #[test]
+    fn test_format_for_notion_nested_bullets() {
+        let blocks = format_for_notion("- parent\n  - child\n- sibling");
+        assert_eq!(blocks.len(), 2);
+        let children = &blocks[0]["bulleted_list_item"]["children"];
+        assert_eq!(children.as_array().unwrap().len(), 1);
+        assert_eq!(children[0]["type"], "bulleted_list_item");
+        assert_eq!(children[0]["bulleted_list_item"]["rich_text"][0]["text"]["content"], "child");
+        assert_eq!(blocks[1]["bulleted_list_item"]["rich_text"][0]["text"]["content"], "sibling");
+    }
+    
+    #[test]
+    fn test_format_for_notion_mixed_nesting_two_levels() {
+        let blocks = format_for_notion("1. step\n    - detail\n\t\t1. sub detail");
+        assert_eq!(blocks.len(), 1);
+        let child = &blocks[0]["numbered_list_item"]["children"][0];
+        assert_eq!(child["type"], "bulleted_list_item");
+        let grandchild = &child["bulleted_list_item"]["children"][0];
+        assert_eq!(grandchild["type"], "numbered_list_item");
+        assert_eq!(grandchild["numbered_list_item"]["rich_text"][0]["text"]["content"], "sub detail");
+    }
+    
+    #[test]
+    fn test_format_for_notion_code_block_single_rich_text() {
+        let text = "```python\nimport os\n\ndef main():\n    print(os.getcwd())\nmain()\n```";
+        let blocks = format_for_notion(text);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0]["type"], "code");
+        assert_eq!(blocks[0]["code"]["language"], "python");
+        let rich_text = blocks[0]["code"]["rich_text"].as_array().unwrap();
+        assert_eq!(rich_text.len(), 1);
+        assert_eq!(
+            rich_text[0]["text"]["content"],
+            "import os\n\ndef main():\n    print(os.getcwd())\nmain()"
+        );
+    }
+    
+    #[test]
+    fn test_split_frontmatter_reads_title_and_tags() {
+        let text = "---\ntitle: \"Trip plan\"\ntags: [travel, 'to do']\ndate: 2024-05-01\n---\n# Day 1\n";
+        let (frontmatter, body) = split_frontmatter(text);
+        assert_eq!(frontmatter.title.as_deref(), Some("Trip plan"));
+        assert_eq!(frontmatter.tags, vec!["travel", "to do"]);
+        assert_eq!(body, "# Day 1\n");
+
+        let (frontmatter, _) = split_frontmatter("---\ntags:\n  - one\n  - two\n---\n");
+        assert_eq!(frontmatter.tags, vec!["one", "two"]);
+    }
+    
+    #[test]
+    fn test_split_frontmatter_leaves_dividers_alone() {
+        for text in ["---\nJust a paragraph\n---\nmore", "---\ntitle: never closed", "intro\n---\ntitle: x\n---"] {
+            assert_eq!(split_frontmatter(text), (Frontmatter::default(), text));
+        }
+    }
+    
+    #[test]
+    fn test_format_for_notion_numbered_list_around_code_block() {
+        let text = "1. Install\n\n```bash\ncargo install mdbook\n```\n\n2. Serve\n3. Open";
+        let blocks = format_for_notion(text);
+        let types: Vec<&str> = blocks.iter().map(|b| b["type"].as_str().unwrap()).collect();
+        assert_eq!(types, vec!["numbered_list_item"; 3]);
+        let children = blocks[0]["numbered_list_item"]["children"].as_array().unwrap();
+        assert_eq!(children[0]["type"], "code");
+        assert_eq!(children[0]["code"]["language"], "bash");
+        assert_eq!(children[0]["code"]["rich_text"][0]["text"]["content"], "cargo install mdbook");
+
+        // A code block ending the list stays top-level
+        let blocks = format_for_notion("1. Install\n```bash\ncargo install mdbook\n```\nDone");
+        assert_eq!(blocks[1]["type"], "code");
+    }
+    
+    #[test]
+    fn test_html_mode_from_env_value() {
+        assert_eq!(html_mode_from(None), HtmlMode::Strip);
+        assert_eq!(html_mode_from(Some(" MAP ".to_string())), HtmlMode::Map);
+        assert_eq!(html_mode_from(Some("keep".to_string())), HtmlMode::Keep);
+        assert_eq!(html_mode_from(Some("other".to_string())), HtmlMode::Strip);
+    }
+    
+    #[test]
+    fn test_convert_inline_html_br() {
+        for mode in [HtmlMode::Strip, HtmlMode::Map] {
+            assert_eq!(convert_inline_html("one<br>two<br/>three<BR />", mode), "one\ntwo\nthree\n");
+        }
+        assert_eq!(convert_inline_html("one<br>two", HtmlMode::Keep), "one<br>two");
+    }
+    
+    #[test]
+    fn test_convert_inline_html_strips_tags() {
+        let text = r#"H<sub>2</sub>O is <b>wet</b>, see <span class="x">Vec<String></span> and `<b>code</b>`"#;
+        assert_eq!(
+            convert_inline_html(text, HtmlMode::Strip),
+            "H2O is wet, see Vec<String> and `<b>code</b>`"
+        );
+        assert_eq!(
+            convert_inline_html(text, HtmlMode::Map),
+            "H2O is **wet**, see Vec<String> and `<b>code</b>`"
+        );
+    }
+    
+    #[test]
+    fn test_format_for_notion_strips_html_by_default() {
+        let blocks = format_for_notion("<i>Hello</i><br>world");
+        let rich_text = blocks[0]["paragraph"]["rich_text"].as_array().unwrap();
+        assert_eq!(rich_text_plain(rich_text), "Hello\nworld");
+    }
+    
+    #[test]
+    fn test_format_for_notion_escaped_heading() {
+        let blocks = format_for_notion("\\# not a heading");
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0]["type"], "paragraph");
+        assert_eq!(blocks[0]["paragraph"]["rich_text"][0]["text"]["content"], "# not a heading");
+    }
+    
+    #[test]
+    fn test_format_for_notion_escaped_bullet() {
+        let blocks = format_for_notion("- real item\n\\- not a bullet\n\\> not a quote\n\\path");
+        assert_eq!(blocks.len(), 4);
+        assert_eq!(blocks[0]["type"], "bulleted_list_item");
+        assert_eq!(blocks[1]["type"], "paragraph");
+        assert_eq!(blocks[1]["paragraph"]["rich_text"][0]["text"]["content"], "- not a bullet");
+        assert_eq!(blocks[2]["paragraph"]["rich_text"][0]["text"]["content"], "> not a quote");
+        // Only punctuation can be escaped
+        assert_eq!(blocks[3]["paragraph"]["rich_text"][0]["text"]["content"], "\\path");
+    }
+    
+    #[test]
+    fn test_format_for_notion_code_block_caption() {
+        let blocks = format_for_notion("```rust\nfn main() {}\n```\ncaption: Entry point of **the** app\nafter");
+        assert_eq!(blocks.len(), 2);
+        let caption = blocks[0]["code"]["caption"].as_array().unwrap();
+        assert_eq!(rich_text_plain(caption), "Entry point of the app");
+        assert_eq!(caption[1]["annotations"]["bold"], true);
+        assert_eq!(blocks[1]["paragraph"]["rich_text"][0]["text"]["content"], "after");
+        assert_eq!(blocks_to_markdown(&blocks[..1]), "```rust\nfn main() {}\n```\ncaption: Entry point of **the** app");
+
+        let plain = format_for_notion("```rust\nfn main() {}\n```\ncaption:\n");
+        assert!(plain[0]["code"].get("caption").is_none());
+        assert_eq!(plain[1]["paragraph"]["rich_text"][0]["text"]["content"], "caption:");
+    }
+    
+    #[test]
+    fn test_format_for_notion_long_code_block_splits_rich_text() {
+        let text = format!("```\n{}\n```", "x".repeat(4500));
+        let blocks = format_for_notion(&text);
+        let rich_text = blocks[0]["code"]["rich_text"].as_array().unwrap();
+        assert_eq!(rich_text.len(), 3);
+        assert!(rich_text.iter().all(|r| r["text"]["content"].as_str().unwrap().len() <= MAX_BLOCK_SIZE));
+    }
+    
+    #[test]
+    fn test_format_for_notion_long_line_splits_rich_text() {
+        let line = "a".repeat(2500);
+        let blocks = format_for_notion(&line);
+        assert_eq!(blocks.len(), 1);
+        let rich_text = blocks[0]["paragraph"]["rich_text"].as_array().unwrap();
+        assert_eq!(rich_text.len(), 2);
+        assert_eq!(rich_text[0]["text"]["content"].as_str().unwrap().len(), 2000);
+        assert_eq!(rich_text[1]["text"]["content"].as_str().unwrap().len(), 500);
+    }
+    
+    #[test]
+    fn test_format_for_notion_image() {
+        let blocks = format_for_notion("![diagram](https://x/y.png)");
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0]["type"], "image");
+        assert_eq!(blocks[0]["image"]["type"], "external");
+        assert_eq!(blocks[0]["image"]["external"]["url"], "https://x/y.png");
+        assert_eq!(blocks[0]["image"]["caption"][0]["text"]["content"], "diagram");
+        assert_eq!(blocks_to_markdown(&blocks), "![diagram](https://x/y.png)");
+    }
+    
+    #[test]
+    fn test_format_for_notion_local_image_falls_back_to_paragraph() {
+        let blocks = format_for_notion("![diagram](./images/y.png)");
+        assert_eq!(blocks[0]["type"], "paragraph");
+        assert_eq!(blocks[0]["paragraph"]["rich_text"][0]["text"]["content"], "![diagram](./images/y.png)");
+    }
+    
+    #[test]
+    fn test_format_for_notion_table() {
+        let text = "| Name | Role |\n|------|:----:|\n| Ada | Engineer |\n| Bob |";
+        let blocks = format_for_notion(text);
+        assert_eq!(blocks.len(), 1);
+        let table = &blocks[0]["table"];
+        assert_eq!(blocks[0]["type"], "table");
+        assert_eq!(table["table_width"], 2);
+        assert_eq!(table["has_column_header"], true);
+        let rows = table["children"].as_array().unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0]["type"], "table_row");
+        assert_eq!(rows[0]["table_row"]["cells"][1][0]["text"]["content"], "Role");
+        assert_eq!(rows[1]["table_row"]["cells"][0][0]["text"]["content"], "Ada");
+        // Ragged row is padded with an empty cell
+        assert_eq!(rows[2]["table_row"]["cells"].as_array().unwrap().len(), 2);
+        assert_eq!(rows[2]["table_row"]["cells"][1], json!([]));
+        assert_eq!(
+            blocks_to_markdown(&blocks),
+            "| Name | Role |\n|---|---|\n| Ada | Engineer |\n| Bob |  |"
+        );
+    }
+    
+    #[test]
+    fn test_format_for_notion_pipe_line_without_separator_is_paragraph() {
+        let blocks = format_for_notion("| not a table |");
+        assert_eq!(blocks[0]["type"], "paragraph");
+    }
+    
+    #[test]
+    fn test_block_size_from_env_value() {
+        assert_eq!(block_size_from(None), MAX_BLOCK_SIZE);
+        assert_eq!(block_size_from(Some("500".to_string())), 500);
+        assert_eq!(block_size_from(Some("5000".to_string())), MAX_BLOCK_SIZE);
+        assert_eq!(block_size_from(Some("0".to_string())), MAX_BLOCK_SIZE);
+        assert_eq!(block_size_from(Some("lots".to_string())), MAX_BLOCK_SIZE);
+    }
+    
+    #[test]
+    fn test_custom_block_size_respected_end_to_end() {
+        let limit = block_size_from(Some("120".to_string()));
+        let text = format!("# Notes\n{}\n## More\n{}", "word ".repeat(60), "text ".repeat(60));
+        let chunks = split_content(&text, limit);
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.len() <= limit);
+            for block in format_for_notion(chunk) {
+                let block_type = block["type"].as_str().unwrap();
+                for segment in block[block_type]["rich_text"].as_array().unwrap() {
+                    assert!(segment["text"]["content"].as_str().unwrap().len() <= limit);
+                }
+            }
+        }
+    }
+    
+    #[test]
+    fn test_format_for_notion_deep_headings() {
+        let blocks = format_for_notion("#### Deep\n###### Deeper\n####### Not a heading");
+        assert_eq!(blocks[0]["type"], "heading_3");
+        assert_eq!(blocks[0]["heading_3"]["rich_text"][0]["text"]["content"], "Deep");
+        assert_eq!(blocks[1]["type"], "heading_3");
+        assert_eq!(blocks[1]["heading_3"]["rich_text"][0]["text"]["content"], "Deeper");
+        assert_eq!(blocks[2]["type"], "paragraph");
+    }
+    
+    #[test]
+    fn test_regexes_compiled_once_across_calls() {
+        let before: *const Regex = &*NUMBERED_LIST_REGEX;
+        for _ in 0..3 {
+            format_for_notion("3. third\n4. fourth");
+            split_content(&format!("# A\n{}\n# B\n{}", "a".repeat(50), "b".repeat(50)), 60);
+        }
+        assert!(std::ptr::eq(before, &*NUMBERED_LIST_REGEX));
+        assert!(std::ptr::eq(&*HEADER_REGEX, &*HEADER_REGEX));
+    }
+
+    #[test]
+    fn test_summarize_blocks_strips_markdown() {
+        let blocks = format_for_notion("# Groceries\n\n- **milk** and `eggs`\n- [bread](https://example.com)");
+        assert_eq!(summarize_blocks(&blocks, SUMMARY_MAX_CHARS), "Groceries milk and eggs bread");
+    }
+
+    #[test]
+    fn test_summarize_blocks_truncates_long_notes() {
+        let blocks = format_for_notion(&"word ".repeat(100));
+        let summary = summarize_blocks(&blocks, 20);
+        assert_eq!(summary, "word word word word…");
+        assert!(summarize_blocks(&[], 20).is_empty());
+    }
+
+    #[test]
+    fn test_format_for_notion_block_equation() {
+        let blocks = format_for_notion("Euler:\n$$\ne^{i\\pi} + 1 = 0\n$$\n$$a^2 + b^2 = c^2$$");
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[1]["type"], "equation");
+        assert_eq!(blocks[1]["equation"]["expression"], "e^{i\\pi} + 1 = 0");
+        assert_eq!(blocks[2]["equation"]["expression"], "a^2 + b^2 = c^2");
+        assert_eq!(blocks_to_markdown(&blocks[1..2]), "$$\ne^{i\\pi} + 1 = 0\n$$");
+    }
+
+    #[test]
+    fn test_parse_inline_equation() {
+        let segments = parse_inline("Energy is $E = mc^2$ exactly");
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[1]["type"], "equation");
+        assert_eq!(segments[1]["equation"]["expression"], "E = mc^2");
+        assert_eq!(rich_text_to_markdown(&segments), "Energy is $E = mc^2$ exactly");
+
+        let prices = parse_inline("costs $5 and $10 today");
+        assert_eq!(prices.len(), 1);
+        assert_eq!(prices[0]["text"]["content"], "costs $5 and $10 today");
+    }
+
+    #[test]
+    fn test_format_for_notion_blank_line_between_list_items() {
+        let blocks = format_for_notion("1. first\n\n2. second\n\n- bullet");
+        let types: Vec<&str> = blocks.iter().map(|b| b["type"].as_str().unwrap()).collect();
+        assert_eq!(types, vec!["numbered_list_item", "numbered_list_item", "paragraph", "bulleted_list_item"]);
+    }
+
+    #[test]
+    fn test_format_for_notion_toggle_children() {
+        let blocks = format_for_notion("Intro\n>>> **Details**\n- one\n- two\n>>> Inner\ndeep\n<<<\n<<<\nAfter");
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[1]["type"], "toggle");
+        assert_eq!(blocks[1]["toggle"]["rich_text"][0]["text"]["content"], "Details");
+        assert_eq!(blocks[1]["toggle"]["rich_text"][0]["annotations"]["bold"], true);
+
+        let children = blocks[1]["toggle"]["children"].as_array().unwrap();
+        let types: Vec<&str> = children.iter().map(|b| b["type"].as_str().unwrap()).collect();
+        assert_eq!(types, vec!["bulleted_list_item", "bulleted_list_item", "toggle"]);
+        assert_eq!(children[2]["toggle"]["children"][0]["paragraph"]["rich_text"][0]["text"]["content"], "deep");
+        assert_eq!(blocks[2]["paragraph"]["rich_text"][0]["text"]["content"], "After");
+    }
+
+    #[test]
+    fn test_format_for_notion_unclosed_toggle_is_not_a_toggle() {
+        let blocks = format_for_notion(">>> no end");
+        assert_eq!(blocks.len(), 1);
+        assert_ne!(blocks[0]["type"], "toggle");
+    }
+
+    #[test]
+    fn test_format_for_notion_note_admonition() {
+        let blocks = format_for_notion("> [!NOTE]\n> Read the **docs** first");
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0]["type"], "callout");
+        assert_eq!(blocks[0]["callout"]["icon"]["emoji"], "ℹ️");
+        assert_eq!(blocks[0]["callout"]["rich_text"][0]["text"]["content"], "Read the ");
+        assert_eq!(blocks[0]["callout"]["rich_text"][1]["annotations"]["bold"], true);
+        assert_eq!(blocks_to_markdown(&blocks), "> [!NOTE]\n> Read the **docs** first");
+    }
+
+    #[test]
+    fn test_format_for_notion_warning_and_unknown_admonitions() {
+        let blocks = format_for_notion("> [!warning] Careful\n> hot stove\n\n> [!DANGER]\n> boom\n\n> plain quote");
+        assert_eq!(blocks[0]["type"], "callout");
+        assert_eq!(blocks[0]["callout"]["icon"]["emoji"], "⚠️");
+        assert_eq!(blocks[0]["callout"]["rich_text"][0]["text"]["content"], "Careful\nhot stove");
+        assert_eq!(blocks[2]["callout"]["icon"]["emoji"], DEFAULT_CALLOUT_EMOJI);
+        assert_eq!(blocks[4]["type"], "quote");
+    }
+
+    #[test]
+    fn test_format_for_notion_code_fence_inside_list_item() {
+        let text = "- Install it:\n  ```bash\n  cargo install mdbook\n    --locked\n  ```\n- Then serve";
+        let blocks = format_for_notion(text);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0]["type"], "bulleted_list_item");
+        let code = &blocks[0]["bulleted_list_item"]["children"][0];
+        assert_eq!(code["type"], "code");
+        assert_eq!(code["code"]["language"], "bash");
+        assert_eq!(code["code"]["rich_text"][0]["text"]["content"], "cargo install mdbook\n  --locked");
+        assert_eq!(blocks[1]["bulleted_list_item"]["rich_text"][0]["text"]["content"], "Then serve");
+    }
+
+    #[test]
+    fn test_format_for_notion_collapses_and_trims_blank_lines() {
+        let blocks = format_for_notion("First\n\n\n\nSecond\n\n\n\n");
+        let types: Vec<&str> = blocks.iter().map(|b| b["type"].as_str().unwrap()).collect();
+        assert_eq!(types, vec!["paragraph", "paragraph", "paragraph"]);
+        assert!(is_empty_paragraph(&blocks[1]));
+        assert!(!is_empty_paragraph(blocks.last().unwrap()));
+    }
+}
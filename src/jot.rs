@@ -1,19 +1,50 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::fs::{self, File};
-use std::io::Write;
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use reqwest::StatusCode;
+use rmcp::handler::server::tool::ToolCallContext;
+use rmcp::service::{RequestContext, RoleServer};
 use rmcp::{Error as McpError, ServerHandler, model::*, schemars, tool};
+use tracing::Instrument;
+use serde_json::{Value, json};
 
-use crate::notion::Notion;
-use crate::formatter::{split_content, format_for_notion};
-
-// Maximum size of a block in the Notion API
-const MAX_BLOCK_SIZE: usize = 2000;
+use crate::notion::{MoveOutcome, Notion, PageCreation, RefType, SortDirection, MAX_QUERY_RESULTS, REF_DB_TITLE, normalize_id, page_title, same_id};
+use crate::util::{NotionError, concurrency, run_bounded};
+use crate::formatter::{
+    blocks_to_markdown, blocks_to_plain_text, count_blocks, max_block_size, split_content,
+    format_for_notion, split_footnotes, split_frontmatter, Frontmatter,
+};
 
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
 pub struct AddPageRequest {
     pub title: String,
     pub content: String,
+    #[schemars(description = "Optional http(s) URL of an image shown as the page cover")]
+    pub cover_url: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct CreateSubpageRequest {
+    #[schemars(description = "ID of the page to create the new page under")]
+    pub parent_page_id: String,
+    pub title: String,
+    pub content: String,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct BulkCreatePagesRequest {
+    #[schemars(description = "Pages to create, each with a title and markdown content")]
+    pub pages: Vec<AddPageRequest>,
 }
 
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
@@ -22,10 +53,85 @@ pub struct UpdatePageRequest {
     pub content: String,
 }
 
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct AppendByTitleRequest {
+    #[schemars(description = "Exact title of the page to append to, case-insensitive")]
+    pub title: String,
+    pub content: String,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct DuplicatePageRequest {
+    #[schemars(description = "ID of the page to copy")]
+    pub page_id: String,
+    #[schemars(description = "Title of the new page")]
+    pub title: String,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct ExportPageRequest {
+    pub page_id: String,
+    #[schemars(description = "File or existing directory to write to (defaults to a file named after the page in the book directory)")]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct PageToChapterRequest {
+    pub page_id: String,
+    #[schemars(description = "Directory of an existing mdbook, absolute or relative to the book directory")]
+    pub book_path: String,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct MovePageRequest {
+    #[schemars(description = "ID of the page to move")]
+    pub page_id: String,
+    #[schemars(description = "ID of the page to move it under")]
+    pub parent_page_id: String,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct RenamePageRequest {
+    pub page_id: String,
+    pub new_title: String,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct SetPagePropertiesRequest {
+    pub page_id: String,
+    #[schemars(description = "Property names and values, e.g. {\"Status\": \"Done\", \"Date\": \"2024-05-01\"}. \
+                              Values are converted by property type; null clears a property")]
+    pub properties: serde_json::Map<String, Value>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct ListPagesRequest {
+    #[schemars(description = "Maximum number of pages to list (default 25, capped by JOTDOWN_MAX_SCAN, 200 unless configured)")]
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct RetrievePageRequest {
+    #[schemars(description = "Title or content of the page")]
+    pub content: String,
+    #[schemars(description = "Which title match wins: \"descending\" for the most recently edited (default), \"ascending\" for the oldest")]
+    pub sort: Option<SortDirection>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct SearchPagesRequest {
+    #[schemars(description = "Text to match against page titles")]
+    pub query: String,
+    #[schemars(description = "Maximum number of pages to return (default 10, max 100)")]
+    pub limit: Option<u32>,
+}
+
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
 pub struct AddMdBook {
     pub name: String,
-    pub content: Vec<MdBookChapter>
+    pub content: Vec<MdBookChapter>,
+    #[schemars(description = "Optional absolute directory to write the book into instead of the default book directory")]
+    pub output_dir: Option<String>,
 }
 
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
@@ -35,20 +141,927 @@ pub struct MdBookChapter {
 }
 
 
+// Number of pages listed when no limit is given
+const DEFAULT_LIST_LIMIT: u32 = 25;
+
+// Number of search results returned when no limit is given
+const DEFAULT_SEARCH_LIMIT: u32 = 10;
+
+/// Title, id and last edit time of each page in a Notion search response
+fn search_results(response: &Value) -> Vec<Value> {
+    response
+        .get("results")
+        .and_then(|v| v.as_array())
+        .map(|results| {
+            results
+                .iter()
+                .map(|page| json!({
+                    "title": page_title(page),
+                    "page_id": page.get("id").and_then(|v| v.as_str()).unwrap_or_default(),
+                    "last_edited": page.get("last_edited_time").and_then(|v| v.as_str()).unwrap_or_default()
+                }))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Search results whose title is exactly `title`, ignoring case and surrounding spaces
+fn pages_titled(response: &Value, title: &str) -> Vec<Value> {
+    let title = title.trim().to_lowercase();
+    search_results(response)
+        .into_iter()
+        .filter(|page| page["title"].as_str().is_some_and(|t| t.trim().to_lowercase() == title))
+        .collect()
+}
+
+/// Name and type of each property in a database schema
+fn schema_properties(database: &Value) -> Vec<Value> {
+    database
+        .get("properties")
+        .and_then(|v| v.as_object())
+        .map(|properties| {
+            properties
+                .iter()
+                .map(|(name, property)| json!({
+                    "name": name,
+                    "type": property.get("type").and_then(|v| v.as_str()).unwrap_or_default()
+                }))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Successful tool result: a JSON object with `ok`, `message` and any extra `fields`
+///
+/// Every tool answers in this shape, failures get `"ok": false` in
+/// [`failed_tool_result`], so clients can always parse the text content.
+fn tool_result(message: impl Into<String>, fields: Value) -> CallToolResult {
+    let mut result = json!({ "ok": true, "message": message.into() });
+    if let (Some(result), Value::Object(fields)) = (result.as_object_mut(), fields) {
+        result.extend(fields);
+    }
+    CallToolResult::success(vec![Content::text(result.to_string())])
+}
+
+/// A failed tool call as a `{"ok": false, "message": ...}` result, with the fields
+/// of the error data (e.g. the candidate pages of an ambiguous title) merged in
+fn failed_tool_result(error: &McpError) -> CallToolResult {
+    let mut result = json!({ "ok": false, "message": error.message });
+    match &error.data {
+        Some(Value::Object(fields)) => {
+            for (key, value) in fields {
+                result.as_object_mut().unwrap().entry(key.clone()).or_insert_with(|| value.clone());
+            }
+        }
+        Some(data) => result["data"] = data.clone(),
+        None => {}
+    }
+    CallToolResult::error(vec![Content::text(result.to_string())])
+}
+
+/// Errors raised by rmcp while dispatching a call, before any tool runs: an
+/// unknown tool name or arguments that do not deserialize
+fn is_dispatch_error(error: &McpError) -> bool {
+    const DISPATCH_MESSAGES: [&str; 3] = ["tool not found", "missing parameter ", "failed to deserialize parameter"];
+    error.code == ErrorCode::METHOD_NOT_FOUND
+        || (error.code == ErrorCode::INVALID_PARAMS && DISPATCH_MESSAGES.iter().any(|m| error.message.starts_with(m)))
+}
+
+/// Page id tool parameter normalized from a pasted URL or dashless id
+fn page_id_param(page_id: &str) -> Result<String, McpError> {
+    normalize_id(page_id).map_err(|e| McpError::invalid_params(format!("error occurred: {}", e), None))
+}
+
+/// Normalize a block id tool parameter, a block link keeps the block id after `#`
+fn block_id_param(block_id: &str) -> Result<String, McpError> {
+    match block_id.split_once('#') {
+        Some((_, fragment)) if normalize_id(fragment).is_ok() => page_id_param(fragment),
+        _ => page_id_param(block_id),
+    }
+}
+
+/// Characters of content accepted by a single tool call by default
+const DEFAULT_MAX_CONTENT_SIZE: usize = 200_000;
+
+/// Content size limit from `JOTDOWN_MAX_CONTENT_SIZE`, in characters
+fn max_content_size() -> usize {
+    max_content_size_from(env::var("JOTDOWN_MAX_CONTENT_SIZE").ok())
+}
+
+fn max_content_size_from(value: Option<String>) -> usize {
+    value
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|size| *size > 0)
+        .unwrap_or(DEFAULT_MAX_CONTENT_SIZE)
+}
+
+/// Reject a blank page title before calling Notion
+fn validate_title(title: &str) -> Result<(), McpError> {
+    if title.trim().is_empty() {
+        return Err(McpError::invalid_params(
+            "error occurred: title is empty, give the page a title (or a `title:` frontmatter field)",
+            None,
+        ));
+    }
+    Ok(())
+}
+
+/// Reject content larger than `max_size` characters before calling Notion
+fn validate_content(content: &str, max_size: usize) -> Result<(), McpError> {
+    let size = content.chars().count();
+    if size > max_size {
+        return Err(McpError::invalid_params(
+            format!(
+                "error occurred: content is {} characters, more than the {} allowed; split it across several calls \
+                 (e.g. create the page, then append_to_page) or raise JOTDOWN_MAX_CONTENT_SIZE",
+                size, max_size
+            ),
+            None,
+        ));
+    }
+    Ok(())
+}
+
+/// `page_id` and `url` fields of a page, leaving out whichever the response lacks
+fn page_fields(response: &Value) -> Value {
+    let mut fields = json!({});
+    for (field, key) in [("page_id", "id"), ("url", "url")] {
+        if let Some(value) = response.get(key).and_then(|v| v.as_str()) {
+            fields[field] = json!(value);
+        }
+    }
+    fields
+}
+
+/// Summary of a newly created page: its title and link
+///
+/// Leaves out the link when the response lacks a `url`.
+fn created_page_message(title: &str, response: &Value) -> String {
+    let mut message = format!("Created page: {}", title);
+    if let Some(url) = response.get("url").and_then(|v| v.as_str()) {
+        message.push_str(&format!(" — {}", url));
+    }
+    message
+}
+
+/// [`created_page_message`] plus a warning naming the content missing from a partially written page
+fn page_creation_message(title: &str, creation: &PageCreation) -> String {
+    let mut message = created_page_message(title, &creation.response);
+    if let Some(failure) = &creation.incomplete {
+        message.push_str(&format!(
+            "\nWarning: the page is incomplete, {}. Blocks after the first {} are missing, \
+             append the remaining content with append_to_page.",
+            failure, failure.blocks_written
+        ));
+    }
+    message
+}
+
+/// Validate an optional cover URL
+///
+/// Returns the trimmed URL when it is http(s), `Ok(None)` when none was
+/// given and the rejected value otherwise.
+fn cover_from(cover_url: Option<&str>) -> Result<Option<&str>, &str> {
+    match cover_url.map(str::trim).filter(|url| !url.is_empty()) {
+        None => Ok(None),
+        Some(url)
+            if (url.starts_with("https://") || url.starts_with("http://"))
+                && !url.contains(char::is_whitespace) =>
+        {
+            Ok(Some(url))
+        }
+        Some(url) => {
+            tracing::warn!("ignoring cover_url '{}', expected an http(s) URL", url);
+            Err(url)
+        }
+    }
+}
+
+/// The requested page title, or the frontmatter title when none was given
+fn page_title_or(title: String, frontmatter: &Frontmatter) -> String {
+    match &frontmatter.title {
+        Some(fallback) if title.trim().is_empty() => fallback.clone(),
+        _ => title,
+    }
+}
+
+/// Take the footnotes out of the whole content, then split and format the rest
+///
+/// Returns each chunk with its blocks, and the footnote blocks that go after
+/// the last chunk, so a definition matches its reference in any chunk.
+fn formatted_chunks(content: &str) -> (Vec<(String, Vec<Value>)>, Vec<Value>) {
+    let (body, notes) = split_footnotes(content);
+    let chunks = split_content(&body, max_block_size())
+        .into_iter()
+        .map(|chunk| {
+            let blocks = format_for_notion(&chunk);
+            (chunk, blocks)
+        })
+        .collect();
+    (chunks, notes)
+}
+
+/// Split and format markdown into Notion blocks, blank content gives no blocks
+fn content_blocks(content: &str) -> Vec<Value> {
+    if content.trim().is_empty() {
+        return Vec::new();
+    }
+    let (chunks, notes) = formatted_chunks(content);
+    chunks.into_iter().flat_map(|(_, blocks)| blocks).chain(notes).collect()
+}
+
+/// Blocks `content` would be sent as, grouped by the chunks `split_content` cuts it into,
+/// with the footnotes that are appended after the last chunk
+fn preview_blocks(content: &str) -> Value {
+    let (chunks, notes) = formatted_chunks(content);
+    let chunks: Vec<Value> = chunks
+        .iter()
+        .enumerate()
+        .map(|(index, (chunk, blocks))| {
+            json!({
+                "chunk": index,
+                "chars": chunk.chars().count(),
+                "blocks": blocks,
+            })
+        })
+        .collect();
+    let block_count: usize = chunks.iter().map(|c| c["blocks"].as_array().map_or(0, |b| b.len())).sum::<usize>() + notes.len();
+    json!({
+        "block_count": block_count,
+        "chunks": chunks,
+        "footnotes": notes,
+    })
+}
+
+/// Word, character and block counts of a page's reconstructed text
+fn page_stats(blocks: &[Value]) -> Value {
+    let text = blocks_to_plain_text(blocks);
+    json!({
+        "words": text.split_whitespace().count(),
+        "chars": text.chars().count(),
+        "blocks": count_blocks(blocks),
+    })
+}
+
+/// Directory books are written into: `custom_root` when set, the home directory otherwise
+fn book_root(custom_root: Option<String>) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    match custom_root.filter(|root| !root.trim().is_empty()) {
+        Some(root) => Ok(PathBuf::from(root.trim())),
+        None => dirs::home_dir().ok_or_else(|| {
+            "could not determine the home directory, set JOTDOWN_BOOK_DIR to choose where books are written".into()
+        }),
+    }
+}
+
+/// Directory a book is written into: `output_dir` when given, otherwise [`book_root`]
+///
+/// `output_dir` must be absolute, and whichever directory is picked is
+/// created and checked for write access before any file is generated.
+fn output_root(output_dir: Option<&str>) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    let root = match output_dir.map(str::trim).filter(|dir| !dir.is_empty()) {
+        Some(dir) if Path::new(dir).is_absolute() => PathBuf::from(dir),
+        Some(dir) => return Err(format!("output_dir '{}' must be an absolute path", dir).into()),
+        None => book_root(env::var("JOTDOWN_BOOK_DIR").ok())?,
+    };
+    ensure_writable(&root)?;
+    Ok(root)
+}
+
+/// Create `dir` if needed and check a file can be written into it
+fn ensure_writable(dir: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let not_writable = |e: std::io::Error| {
+        format!("directory '{}' is not writable ({}), choose another output_dir", dir.display(), e)
+    };
+    fs::create_dir_all(dir).map_err(not_writable)?;
+    let probe = dir.join(".jotdown-write-check");
+    fs::write(&probe, b"").map_err(not_writable)?;
+    let _ = fs::remove_file(&probe);
+    Ok(())
+}
+
+/// Turn a book name into a single safe directory name
+///
+/// Path separators and whitespace become `-`, characters that are invalid in
+/// file names are dropped, and names containing `..` are rejected so a book
+/// can never be written outside of the output directory.
+fn sanitize_book_name(name: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    if name.contains("..") {
+        return Err(format!("invalid book name '{}': must not contain '..'", name).into());
+    }
+    let replaced: String = name
+        .trim()
+        .chars()
+        .filter(|c| !c.is_control() && !matches!(c, ':' | '*' | '?' | '"' | '<' | '>' | '|'))
+        .map(|c| if c == '/' || c == '\\' || c.is_whitespace() { '-' } else { c })
+        .collect();
+    let sanitized = replaced
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+        .trim_matches('.')
+        .to_string();
+    if sanitized.is_empty() {
+        return Err(format!("invalid book name '{}': no usable characters", name).into());
+    }
+    Ok(sanitized)
+}
+
+/// Lowercase, hyphenated form of a name, suitable for a file name
+fn slugify(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Write an exported page as a markdown file and return its absolute path
+///
+/// `path` may name the file or an existing directory; without it the file is
+/// written into `default_dir`. File names are derived from the page title.
+fn write_page_export(
+    path: Option<&str>,
+    default_dir: &Path,
+    title: &str,
+    markdown: &str,
+) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    let file_name = match slugify(title) {
+        slug if slug.is_empty() => "page.md".to_string(),
+        slug => format!("{}.md", slug),
+    };
+    let file_path = match path.map(str::trim).filter(|p| !p.is_empty()) {
+        Some(path) if Path::new(path).is_dir() => Path::new(path).join(file_name),
+        Some(path) => PathBuf::from(path),
+        None => default_dir.join(file_name),
+    };
+    if let Some(parent) = file_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let content = if title.trim().is_empty() {
+        format!("{}\n", markdown)
+    } else {
+        format!("# {}\n\n{}\n", title.trim(), markdown)
+    };
+    fs::write(&file_path, content)?;
+    Ok(fs::canonicalize(&file_path)?)
+}
+
+/// Markdown file name for each chapter, derived from the chapter names
+///
+/// Chapters whose names slugify to the same value get a numeric suffix,
+/// and names that would clash with README.md or SUMMARY.md are suffixed too.
+fn chapter_filenames(content: &[MdBookChapter]) -> Vec<String> {
+    let mut used = reserved_chapter_names();
+    content
+        .iter()
+        .map(|chapter| unique_chapter_filename(&chapter.name, &mut used))
+        .collect()
+}
+
+/// File stems chapters can't use because the book's own files have them
+fn reserved_chapter_names() -> HashSet<String> {
+    ["readme", "summary"].iter().map(|s| s.to_string()).collect()
+}
+
+/// Slugified file name for a chapter that isn't in `used` yet, which it is added to
+fn unique_chapter_filename(name: &str, used: &mut HashSet<String>) -> String {
+    let slug = match slugify(name) {
+        slug if slug.is_empty() => "chapter".to_string(),
+        slug => slug,
+    };
+    let mut candidate = slug.clone();
+    let mut index = 2;
+    while !used.insert(candidate.clone()) {
+        candidate = format!("{}-{}", slug, index);
+        index += 1;
+    }
+    format!("{}.md", candidate)
+}
+
+/// Directory of an existing book, absolute or relative to [`book_root`]
+fn resolve_book_dir(book_path: &str) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    let book_dir = Path::new(book_path.trim());
+    if book_dir.is_absolute() {
+        Ok(book_dir.to_path_buf())
+    } else {
+        book_root(env::var("JOTDOWN_BOOK_DIR").ok()).map(|root| root.join(book_dir))
+    }
+}
+
+/// Add a chapter file to an existing mdbook and link it at the end of SUMMARY.md
+///
+/// The file name is slugified from the title and suffixed when a chapter
+/// file with that name already exists.
+fn add_chapter(book_dir: &Path, title: &str, markdown: &str) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    let src_dir = book_dir.join("src");
+    let summary_path = src_dir.join("SUMMARY.md");
+    if !summary_path.is_file() {
+        return Err(format!("{} is not an mdbook, src/SUMMARY.md is missing", book_dir.display()).into());
+    }
+
+    let mut used = reserved_chapter_names();
+    for entry in fs::read_dir(&src_dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "md")
+            && let Some(stem) = path.file_stem().and_then(|s| s.to_str())
+        {
+            used.insert(stem.to_lowercase());
+        }
+    }
+    let title = if title.trim().is_empty() { "Untitled" } else { title.trim() };
+    let file_name = unique_chapter_filename(title, &mut used);
+    fs::write(src_dir.join(&file_name), format!("# {}\n\n{}\n", title, markdown))?;
+
+    let mut summary = fs::read_to_string(&summary_path)?;
+    if !summary.is_empty() && !summary.ends_with('\n') {
+        summary.push('\n');
+    }
+    summary.push_str(&format!("* [{}]({})\n", title, file_name));
+    fs::write(&summary_path, summary)?;
+    Ok(src_dir.join(file_name))
+}
+
+/// Chapter title of a markdown file: its first `# ` heading, or the file stem
+fn chapter_title(path: &Path) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let heading = fs::read_to_string(path)?
+        .lines()
+        .find_map(|line| line.strip_prefix("# ").map(|title| title.trim().to_string()))
+        .filter(|title| !title.is_empty());
+    Ok(heading.unwrap_or_else(|| {
+        path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default()
+    }))
+}
+
+/// Rewrite SUMMARY.md of an mdbook to link every chapter file in `src/`
+///
+/// Chapters already linked keep their order, files not linked yet follow in
+/// file name order. The README stays first as the introduction.
+///
+/// Returns the number of chapters linked.
+fn rebuild_summary(book_dir: &Path) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let src_dir = book_dir.join("src");
+    if !src_dir.is_dir() {
+        return Err(format!("{} is not an mdbook, the src directory is missing", book_dir.display()).into());
+    }
+    let summary_path = src_dir.join("SUMMARY.md");
+
+    let reserved = reserved_chapter_names();
+    let mut files = Vec::new();
+    for entry in fs::read_dir(&src_dir)? {
+        let path = entry?.path();
+        if path.is_file()
+            && path.extension().is_some_and(|ext| ext == "md")
+            && let Some(stem) = path.file_stem().and_then(|s| s.to_str())
+            && !reserved.contains(&stem.to_lowercase())
+            && let Some(name) = path.file_name().and_then(|s| s.to_str())
+        {
+            files.push(name.to_string());
+        }
+    }
+    files.sort();
+
+    // Files linked in the current summary, in the order they are linked
+    let existing = fs::read_to_string(&summary_path).unwrap_or_default();
+    let linked: Vec<&str> = existing
+        .lines()
+        .filter_map(|line| {
+            let start = line.find("](")? + 2;
+            let end = start + line[start..].find(')')?;
+            Some(line[start..end].trim())
+        })
+        .collect();
+    files.sort_by_key(|name| linked.iter().position(|link| link == name).unwrap_or(usize::MAX));
+
+    let mut summary = String::from("# Summary\n");
+    if src_dir.join("README.md").is_file() {
+        summary.push_str("* [Introduction](README.md)\n");
+    }
+    for name in &files {
+        summary.push_str(&format!("* [{}]({})\n", chapter_title(&src_dir.join(name))?, name));
+    }
+    fs::write(&summary_path, summary)?;
+    Ok(files.len())
+}
+
+/// A chapter of an existing mdbook read for import
+#[derive(Debug, Clone, PartialEq)]
+struct BookChapter {
+    title: String,
+    markdown: String,
+}
+
+/// Title of a book from the `title` key of its book.toml, or the directory name
+fn book_title(book_dir: &Path) -> String {
+    let configured = fs::read_to_string(book_dir.join("book.toml"))
+        .ok()
+        .and_then(|toml| {
+            toml.lines().find_map(|line| {
+                let (key, value) = line.split_once('=')?;
+                let value = value.trim();
+                let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+                (key.trim() == "title").then(|| value.replace("\\\"", "\"").replace("\\\\", "\\"))
+            })
+        })
+        .filter(|title| !title.trim().is_empty());
+    configured.unwrap_or_else(|| {
+        book_dir.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default()
+    })
+}
+
+/// Title and file of every chapter linked from SUMMARY.md, in order
+///
+/// Draft chapters (`[Title]()`) and links to anything but a markdown file are skipped.
+fn summary_links(summary: &str) -> Vec<(String, String)> {
+    summary
+        .lines()
+        .filter_map(|line| {
+            let open = line.find('[')? + 1;
+            let close = open + line[open..].find("](")?;
+            let end = close + 2 + line[close + 2..].find(')')?;
+            let file = line[close + 2..end].trim();
+            file.ends_with(".md").then(|| (line[open..close].trim().to_string(), file.to_string()))
+        })
+        .collect()
+}
+
+/// Read the chapters of an mdbook in SUMMARY.md order
+fn read_book_chapters(book_dir: &Path) -> Result<Vec<BookChapter>, Box<dyn std::error::Error + Send + Sync>> {
+    let src_dir = book_dir.join("src");
+    let summary = fs::read_to_string(src_dir.join("SUMMARY.md"))
+        .map_err(|e| format!("{} is not an mdbook, src/SUMMARY.md can't be read: {}", book_dir.display(), e))?;
+    summary_links(&summary)
+        .into_iter()
+        .map(|(title, file)| {
+            let markdown = fs::read_to_string(src_dir.join(&file))
+                .map_err(|e| format!("chapter {} can't be read: {}", file, e))?;
+            Ok(BookChapter { title, markdown })
+        })
+        .collect()
+}
+
+/// Most pages a search or list scans unless `JOTDOWN_MAX_SCAN` is set
+const DEFAULT_MAX_SCAN: u32 = 200;
+
+/// Cap on the pages any search or list tool fetches from the database
+fn max_scan() -> u32 {
+    max_scan_from(env::var("JOTDOWN_MAX_SCAN").ok())
+}
+
+fn max_scan_from(value: Option<String>) -> u32 {
+    value
+        .and_then(|v| v.trim().parse::<u32>().ok())
+        .filter(|cap| *cap > 0)
+        .map_or(DEFAULT_MAX_SCAN, |cap| cap.min(MAX_QUERY_RESULTS))
+}
+
+/// Note added to a tool message when the scan cap left pages out
+fn truncation_note(max_scan: u32) -> String {
+    format!(
+        " (results truncated: only the {} most recently edited pages were scanned, raise JOTDOWN_MAX_SCAN to scan more)",
+        max_scan
+    )
+}
+
+/// Matches of a content search, and whether the scan cap left pages unsearched
+#[derive(Debug)]
+struct ContentSearch {
+    matches: Vec<PageMatch>,
+    truncated: bool,
+}
+
+// Weight of a title match relative to a single match in the body
+const TITLE_MATCH_SCORE: usize = 10;
+
+/// A page matching a content search
+#[derive(Debug, Clone, PartialEq)]
+struct PageMatch {
+    page_id: String,
+    title: String,
+    score: usize,
+}
+
+/// Rank `(page_id, title, body)` triples by case-insensitive matches of `query`
+///
+/// Title matches weigh more than body matches; pages without any match are
+/// dropped and ties keep their original order.
+fn rank_pages(query: &str, pages: &[(String, String, String)]) -> Vec<PageMatch> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let mut matches: Vec<PageMatch> = pages
+        .iter()
+        .map(|(page_id, title, body)| {
+            let title_score = if title.to_lowercase().contains(&query) { TITLE_MATCH_SCORE } else { 0 };
+            let body_score = body.to_lowercase().matches(&query).count();
+            PageMatch {
+                page_id: page_id.clone(),
+                title: title.clone(),
+                score: title_score + body_score,
+            }
+        })
+        .filter(|m| m.score > 0)
+        .collect();
+    matches.sort_by_key(|m| std::cmp::Reverse(m.score));
+    matches
+}
+
+/// Quote a value as a TOML basic string
+fn toml_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Time zone named by `JOTDOWN_TZ` (an IANA name such as `Europe/Berlin`), UTC when unset or unknown
+fn timezone_from(value: Option<String>) -> Tz {
+    let Some(name) = value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty()) else {
+        return Tz::UTC;
+    };
+    name.parse::<Tz>().unwrap_or_else(|_| {
+        tracing::warn!("unknown time zone '{}' in JOTDOWN_TZ, using UTC", name);
+        Tz::UTC
+    })
+}
+
+/// Title of the page quick notes are appended to
+const INBOX_TITLE: &str = "Inbox";
+
+/// Bulleted block for a quick note, prefixed with the time it was taken
+fn quick_note_blocks(text: &str, time: DateTime<Utc>, zone: Tz) -> Vec<Value> {
+    let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    format_for_notion(&format!("- {} — {}", format_timestamp(time, zone), line))
+}
+
+/// Format a timestamp in the given zone, e.g. `2024-05-01 14:30 CEST`
+fn format_timestamp(time: DateTime<Utc>, zone: Tz) -> String {
+    time.with_timezone(&zone).format("%Y-%m-%d %H:%M %Z").to_string()
+}
+
+/// Write the files of an mdbook (book.toml, README, SUMMARY and chapters) into `book_dir`
+fn write_mdbook(book_dir: &Path, title: &str, content: &[MdBookChapter]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let src_dir = book_dir.join("src");
+    fs::create_dir_all(&src_dir)?;
+    // Write book.toml
+    let authors = env::var("JOTDOWN_BOOK_AUTHOR")
+        .ok()
+        .filter(|author| !author.trim().is_empty())
+        .map(|author| toml_string(author.trim()))
+        .unwrap_or_default();
+    let mut book_toml = File::create(book_dir.join("book.toml"))?;
+    writeln!(book_toml, "[book]")?;
+    writeln!(book_toml, "title = {}", toml_string(title))?;
+    writeln!(book_toml, "authors = [{}]", authors)?;
+    writeln!(book_toml, "language = \"en\"")?;
+    writeln!(book_toml, "src = \"src\"")?;
+    // Write README.md
+    fs::write(
+        src_dir.join("README.md"),
+        format!(
+            "# {}\n\nWelcome to {}!\n\n_Generated on {}_\n",
+            title,
+            title,
+            format_timestamp(Utc::now(), timezone_from(env::var("JOTDOWN_TZ").ok()))
+        ),
+    )?;
+    // Write SUMMARY.md
+    let mut summary = File::create(src_dir.join("SUMMARY.md"))?;
+    writeln!(summary, "# Summary")?;
+    writeln!(summary, "* [Introduction](README.md)")?;
+    // Write chapters
+    for (chapter, chapter_filename) in content.iter().zip(chapter_filenames(content)) {
+        writeln!(summary, "* [{}]({})", chapter.name, chapter_filename)?;
+        fs::write(src_dir.join(&chapter_filename), &chapter.content)?;
+    }
+    Ok(())
+}
+
+/// Run `write` against a staging directory next to `dir`, then move the result into place
+///
+/// A failed write removes the staging directory so no half-written book is
+/// left behind. An existing directory at `dir` is only replaced once the new
+/// one is complete, and is restored if the swap fails.
+fn write_atomically(
+    dir: &Path,
+    write: impl FnOnce(&Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let name = dir
+        .file_name()
+        .ok_or_else(|| format!("invalid book directory '{}'", dir.display()))?
+        .to_string_lossy();
+    let staging = dir.with_file_name(format!(".{}.partial-{}", name, new_request_id()));
+    if let Err(e) = write(&staging) {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+
+    let backup = dir.with_file_name(format!(".{}.previous-{}", name, new_request_id()));
+    let replacing = dir.exists();
+    if replacing && let Err(e) = fs::rename(dir, &backup) {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(e.into());
+    }
+    if let Err(e) = fs::rename(&staging, dir) {
+        let _ = fs::remove_dir_all(&staging);
+        if replacing {
+            let _ = fs::rename(&backup, dir);
+        }
+        return Err(e.into());
+    }
+    if replacing {
+        let _ = fs::remove_dir_all(&backup);
+    }
+    Ok(())
+}
+
+// Name of the mdbook executable looked up on PATH
+const MDBOOK_BINARY: &str = "mdbook";
+
+/// Percent-encoded `file://` URL of a directory, None for a relative path
+fn directory_url(dir: &Path) -> Option<String> {
+    reqwest::Url::from_directory_path(dir).ok().map(String::from)
+}
+
+/// Check that the mdbook binary can be run, with install instructions when it can't
+fn ensure_mdbook_installed(binary: &str) -> Result<(), McpError> {
+    match Command::new(binary)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+    {
+        Ok(status) if status.success() => Ok(()),
+        _ => Err(McpError::internal_error(
+            format!(
+                "error occurred: the {} binary was not found on PATH, ask the user to install it with `cargo install mdbook` and try again",
+                binary
+            ),
+            None,
+        )),
+    }
+}
+
+/// Run `mdbook build` for a book, returning the directory the static site was written to
+///
+/// mdbook reports problems such as broken SUMMARY links on stderr, so its
+/// error lines (or all of stderr when none are marked) become the error.
+fn build_mdbook_site(binary: &str, book_dir: &Path) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    if !book_dir.join("book.toml").is_file() {
+        return Err(format!("{} is not an mdbook, book.toml is missing", book_dir.display()).into());
+    }
+    let output = Command::new(binary)
+        .arg("build")
+        .current_dir(book_dir)
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let errors: Vec<&str> = stderr.lines().filter(|line| line.contains("ERROR")).collect();
+        let details = if errors.is_empty() { stderr.trim().to_string() } else { errors.join("\n") };
+        return Err(format!("mdbook build failed ({}): {}", output.status, details).into());
+    }
+    Ok(book_dir.join("book"))
+}
+
+// How long to wait for `mdbook serve` to report the address it is serving on
+const SERVE_STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A background `mdbook serve` process
+#[derive(Debug)]
+struct MdBookServer {
+    child: Child,
+    url: String,
+}
+
+/// Let the OS pick a free local port
+fn free_port() -> std::io::Result<u16> {
+    Ok(TcpListener::bind("127.0.0.1:0")?.local_addr()?.port())
+}
+
+/// Send every line of a child process stream over a channel, draining it until it closes
+fn forward_lines<R: Read + Send + 'static>(stream: Option<R>, sender: mpsc::Sender<String>) {
+    if let Some(stream) = stream {
+        thread::spawn(move || {
+            for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                let _ = sender.send(line);
+            }
+        });
+    }
+}
+
+/// URL from the `Serving on: http://...` line mdbook logs once it is listening
+fn serving_url(line: &str) -> Option<String> {
+    let (_, address) = line.split_once("Serving on:")?;
+    let url = address.trim();
+    url.starts_with("http").then(|| url.to_string())
+}
+
+/// Start `mdbook serve` for a book in the background and wait for its URL
+///
+/// A free port is picked up front so a busy default port (3000) does not
+/// stop the book from being served.
+fn spawn_mdbook_server(dir: &Path) -> Result<MdBookServer, Box<dyn std::error::Error + Send + Sync>> {
+    let port = free_port()?;
+    let mut child = Command::new(MDBOOK_BINARY)
+        .arg("serve")
+        .arg("-o")
+        .arg("-p")
+        .arg(port.to_string())
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let (sender, receiver) = mpsc::channel();
+    forward_lines(child.stdout.take(), sender.clone());
+    forward_lines(child.stderr.take(), sender);
+
+    let deadline = Instant::now() + SERVE_STARTUP_TIMEOUT;
+    let mut output = Vec::new();
+    loop {
+        match receiver.recv_timeout(Duration::from_millis(200)) {
+            Ok(line) => {
+                if let Some(url) = serving_url(&line) {
+                    return Ok(MdBookServer { child, url });
+                }
+                output.push(line);
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => thread::sleep(Duration::from_millis(50)),
+        }
+
+        if let Some(status) = child.try_wait()? {
+            let output = output.join("\n");
+            if output.to_lowercase().contains("address already in use") {
+                return Err(format!("port {} is already in use: {}", port, output).into());
+            }
+            return Err(format!("mdbook serve exited with {}: {}", status, output).into());
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err("timed out waiting for mdbook serve to start".into());
+        }
+    }
+}
+
 #[derive(Debug, Clone, serde::Deserialize)]
 pub struct Jotter {
     data_store: Notion,
+    // Running `mdbook serve` processes keyed by book path
+    #[serde(skip)]
+    mdbook_servers: Arc<Mutex<HashMap<PathBuf, MdBookServer>>>,
+    // Reference database id resolved by the first lookup
+    #[serde(skip)]
+    ref_db_cache: Arc<Mutex<Option<String>>>,
 }
 
 #[tool(tool_box)]
 impl Jotter {
     pub fn new(store: Notion) -> Self {
-        Self { data_store: store }
+        Self {
+            data_store: store,
+            mdbook_servers: Arc::default(),
+            ref_db_cache: Arc::default(),
+        }
     }
 
     async fn search_ref_db(&self) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        let ref_db_name = "Jot It Down MCP server database";
-        match self.data_store.search_ref(ref_db_name, "database").await {
+        if let Some(db_id) = self.ref_db_cache.lock().unwrap().clone() {
+            return Ok(db_id);
+        }
+        let db_id = self.lookup_ref_db().await?;
+        *self.ref_db_cache.lock().unwrap() = Some(db_id.clone());
+        Ok(db_id)
+    }
+
+    /// Forget the cached reference database id when Notion no longer finds it
+    fn invalidate_ref_db_on_not_found(&self, error: &anyhow::Error) {
+        if error.downcast_ref::<NotionError>().is_some_and(|e| e.status == StatusCode::NOT_FOUND) {
+            tracing::warn!("reference database not found, looking it up again on the next call");
+            self.ref_db_cache.lock().unwrap().take();
+        }
+    }
+
+    /// Append already validated content to the end of a page
+    async fn append_content(&self, page_id: &str, content: &str) -> Result<CallToolResult, McpError> {
+        let all_blocks = content_blocks(content);
+        match self.data_store.append_blocks(page_id, &all_blocks).await {
+            Ok(_) => Ok(tool_result(
+                format!("Appended {} blocks to page {}", all_blocks.len(), page_id),
+                json!({ "page_id": page_id, "blocks": all_blocks.len() }),
+            )),
+            Err(e) => Err(McpError::internal_error(
+                format!("error occurred: error appending to page: {}", e),
+                None,
+            )),
+        }
+    }
+
+    async fn lookup_ref_db(&self) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        // Oldest first, so a duplicate created later never replaces the original
+        match self.data_store.search_ref(REF_DB_TITLE, RefType::Database, SortDirection::Ascending).await {
             Ok((_, json_resp)) => {
                 if let Some(db_id) = json_resp
                     .get("results")
@@ -67,7 +1080,7 @@ impl Jotter {
 
     async fn search_ref_page(&self) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
         let ref_page_name = "Jot It Down";
-        match self.data_store.search_ref(ref_page_name, "page").await {
+        match self.data_store.search_ref(ref_page_name, RefType::Page, SortDirection::Ascending).await {
             Ok((_, json_resp)) => {
                 if let Some(page_id) = json_resp
                     .get("results")
@@ -89,11 +1102,10 @@ impl Jotter {
         page_id: &str,
     ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
         match self.data_store.create_database(page_id).await {
+            // The created database object is returned directly, not as search results
             Ok((_, json_resp)) => {
                 if let Some(db_id) = json_resp
-                    .get("results")
-                    .and_then(|v| v.get(0))
-                    .and_then(|v| v.get("id"))
+                    .get("id")
                     .and_then(|v| v.as_str())
                 {
                     Ok(db_id.to_string())
@@ -105,64 +1117,110 @@ impl Jotter {
         }
     }
 
-
-    fn bundle_mdbook(&self, name: &str, content: Vec<MdBookChapter>) -> Result<std::path::PathBuf, Box<dyn std::error::Error + Send + Sync>> {
-        let file_path = std::env::home_dir().unwrap().join(name);
-        fs::create_dir_all(&file_path)?;
-        // Write README.md
-        let readme_path = file_path.join("README.md");
-        fs::write(&readme_path, "# My MdBook\nWelcome to my book!")?;
-        // Write SUMMARY.md
-        let summary_path = file_path.join("src/SUMMARY.md");
-        if let Some(parent) = summary_path.parent() {
-            fs::create_dir_all(parent)?;
-        }
-        let mut summary = File::create(&summary_path)?;
-        writeln!(summary, "# Summary")?;
-        writeln!(summary, "* [Introduction](README.md)")?;
-        // Write chapters
-        for (i, chapter) in content.iter().enumerate() {
-            let chapter_filename = format!("chapter_{}.md", i);
-            writeln!(summary, "* [{}]({})", chapter.name, chapter_filename)?;
-            let chapter_path = file_path.join(format!("src/{}", chapter_filename));
-            fs::write(&chapter_path, &chapter.content)?;
+    /// ID of the reference database, creating it under the reference page when missing
+    async fn ref_db_id(&self) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        if let Ok(db_id) = self.search_ref_db().await {
+            return Ok(db_id);
         }
-        let path_clone = file_path.clone();
-        Ok(path_clone)
+        let page_id = self.search_ref_page().await.map_err(|_| "ref page not found")?;
+        let db_id = self.create_ref_db(page_id.as_str())
+            .await
+            .map_err(|e| format!("error creating database: {}", e))?;
+        *self.ref_db_cache.lock().unwrap() = Some(db_id.clone());
+        Ok(db_id)
     }
 
-    async fn open_mdbook (&self, book_path: String) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-            let dir = Path::new(book_path.as_str());
+    /// ID of the Inbox page in the reference database, creating it when missing
+    async fn inbox_page_id(&self) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let db_id = self.ref_db_id().await?;
+        let (_, json_resp) = self.data_store.search_ref(INBOX_TITLE, RefType::Page, SortDirection::Ascending).await?;
+        let inbox = json_resp
+            .get("results")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .find(|page| {
+                page_title(page) == INBOX_TITLE
+                    && page["parent"]["database_id"].as_str().is_some_and(|parent| same_id(parent, &db_id))
+            })
+            .and_then(|page| page.get("id"))
+            .and_then(|v| v.as_str());
+        if let Some(page_id) = inbox {
+            return Ok(page_id.to_string());
+        }
+        let creation = self.data_store.create_page_with_blocks(&db_id, INBOX_TITLE, &[], None, &[]).await?;
+        creation.response
+            .get("id")
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .ok_or_else(|| "inbox page id not found".into())
+    }
 
-            let mut child = Command::new("mdbook")
-            .arg("serve")
-            .arg("-o")
-            .current_dir(dir)
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .spawn()?;
+    fn bundle_mdbook(&self, name: &str, content: Vec<MdBookChapter>, output_dir: Option<&str>) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+        let file_path = output_root(output_dir)?.join(sanitize_book_name(name)?);
+        write_atomically(&file_path, |dir| write_mdbook(dir, name, &content))?;
+        Ok(file_path)
+    }
 
-            child.wait()?;
+    async fn open_mdbook(&self, book_path: String) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let dir = fs::canonicalize(&book_path).unwrap_or_else(|_| PathBuf::from(&book_path));
 
-            Ok(())
+        // Reuse a server that is still running for this book
+        if let Some(server) = self.mdbook_servers.lock().unwrap().get_mut(&dir)
+            && matches!(server.child.try_wait(), Ok(None))
+        {
+            return Ok(server.url.clone());
         }
 
-    #[tool(description = "Retrieve a page by its title or content to get the page id")]
-    async fn retrieve_page(&self, #[tool(param)] content: String) -> Result<CallToolResult, McpError> {
-        match self.data_store.search_ref(&content, "page").await {
-            Ok((_, json_resp)) => {
+        let book_dir = dir.clone();
+        let server = tokio::task::spawn_blocking(move || spawn_mdbook_server(&book_dir)).await??;
+        let url = server.url.clone();
+        tracing::info!("serving mdbook {} at {} (pid {})", dir.display(), url, server.child.id());
+        self.mdbook_servers.lock().unwrap().insert(dir, server);
+        Ok(url)
+    }
+
+    /// Kill the `mdbook serve` process for a book, returning its pid if one was running
+    fn close_mdbook(&self, book_path: &str) -> Result<Option<u32>, Box<dyn std::error::Error + Send + Sync>> {
+        let dir = fs::canonicalize(book_path).unwrap_or_else(|_| PathBuf::from(book_path));
+        let Some(mut server) = self.mdbook_servers.lock().unwrap().remove(&dir) else {
+            return Ok(None);
+        };
+        // The server may already have exited on its own
+        if server.child.try_wait()?.is_some() {
+            return Ok(None);
+        }
+        server.child.kill()?;
+        server.child.wait()?;
+        Ok(Some(server.child.id()))
+    }
+
+    #[tool(description = "Retrieve a page by its title or content to get the page id, the most recently edited match by default")]
+    async fn retrieve_page(
+        &self,
+        #[tool(aggr)] RetrievePageRequest { content, sort }: RetrievePageRequest,
+    ) -> Result<CallToolResult, McpError> {
+        match self.data_store.search_ref(&content, RefType::Page, sort.unwrap_or_default()).await {
+            Ok((_, json_resp)) => {
                 if let Some(page_id) = json_resp
                     .get("results")
                     .and_then(|v| v.get(0))
                     .and_then(|v| v.get("id"))
                     .and_then(|v| v.as_str())
                 {
-                    Ok(CallToolResult::success(vec![Content::text(page_id)]))
+                    Ok(tool_result(format!("Found page {}", page_id), json!({ "page_id": page_id })))
                 } else {
-                    Err(McpError::internal_error(
-                        "error occurred: error finding page",
-                        None,
-                    ))
+                    // Notion search only matches titles, fall back to the page bodies
+                    match self.search_content_matches(&content, max_scan()).await {
+                        Ok(search) if !search.matches.is_empty() => Ok(tool_result(
+                            format!("Found page {} by its content", search.matches[0].page_id),
+                            json!({ "page_id": search.matches[0].page_id }),
+                        )),
+                        _ => Err(McpError::internal_error(
+                            "error occurred: error finding page",
+                            None,
+                        )),
+                    }
                 }
             }
             Err(e) => Err(McpError::internal_error(
@@ -172,129 +1230,2059 @@ impl Jotter {
         }
     }
 
-    #[tool(description = "Updates a page for given content and page id")]
-    async fn update_page(&self, #[tool(aggr)] UpdatePageRequest { page_id, content }: UpdatePageRequest) -> Result<CallToolResult, McpError> {
-        // Split and format the content
-        let content_chunks = split_content(&content, MAX_BLOCK_SIZE);
-        let mut all_blocks = Vec::new();
-        
-        for chunk in content_chunks {
-            all_blocks.extend(format_for_notion(&chunk));
+    #[tool(description = "Search pages by title, returning up to `limit` matches with their title, page id and last edit time, most recently edited first")]
+    async fn search_pages(
+        &self,
+        #[tool(aggr)] SearchPagesRequest { query, limit }: SearchPagesRequest,
+    ) -> Result<CallToolResult, McpError> {
+        match self.data_store.search_pages(&query, limit.unwrap_or(DEFAULT_SEARCH_LIMIT)).await {
+            Ok((_, json_resp)) => {
+                let results = search_results(&json_resp);
+                Ok(tool_result(format!("Found {} pages", results.len()), json!({ "results": results })))
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("error occurred: error searching pages: {}", e),
+                None,
+            )),
         }
-        
-        // Use the new update_page_with_blocks method
-        match self.data_store.update_page_with_blocks(page_id.as_str(), &all_blocks).await {
-            Ok((_, val)) => Ok(CallToolResult::success(vec![Content::text(val.to_string())])),
+    }
+
+    /// Search the title and body of the `max_scan` most recent pages in the Jotdown database
+    async fn search_content_matches(&self, query: &str, max_scan: u32) -> Result<ContentSearch, Box<dyn std::error::Error + Send + Sync>> {
+        let db_id = self.search_ref_db().await?;
+        let (_, json_resp) = self.data_store.query_database(&db_id, max_scan).await?;
+        let truncated = json_resp.get("has_more").and_then(|v| v.as_bool()).unwrap_or(false);
+        let results = json_resp.get("results").and_then(|v| v.as_array()).map(|v| v.as_slice()).unwrap_or(&[]);
+        let fetched = run_bounded(
+            results.iter().filter_map(|page| Some((page.get("id")?.as_str()?, page))),
+            concurrency(),
+            |(page_id, page)| async move {
+                let blocks = self.data_store.fetch_all_blocks(page_id).await?;
+                Ok::<_, anyhow::Error>((page_id.to_string(), page_title(page), blocks_to_markdown(&blocks)))
+            },
+        )
+        .await;
+        let pages = fetched.into_iter().collect::<Result<Vec<_>, _>>()?;
+        Ok(ContentSearch { matches: rank_pages(query, &pages), truncated })
+    }
+
+    #[tool(description = "Search the title and content of stored pages for a phrase, returning matching page ids best match first")]
+    async fn search_content(&self, #[tool(param)] query: String) -> Result<CallToolResult, McpError> {
+        let max_scan = max_scan();
+        match self.search_content_matches(&query, max_scan).await {
+            Ok(search) => {
+                let results: Vec<Value> = search
+                    .matches
+                    .iter()
+                    .map(|m| json!({ "page_id": m.page_id, "title": m.title, "score": m.score }))
+                    .collect();
+                let mut message = format!("Found {} matching pages", results.len());
+                if search.truncated {
+                    message.push_str(&truncation_note(max_scan));
+                }
+                Ok(tool_result(message, json!({ "results": results, "truncated": search.truncated })))
+            }
             Err(e) => Err(McpError::internal_error(
-                format!("error occurred: error updating page: {}", e),
+                format!("error occurred: error searching page content: {}", e),
                 None,
             )),
         }
     }
 
-    #[tool(description = "Create a new page")]
-    async fn create_new_page(
+    #[tool(description = "List the pages stored in the Jotdown database with their titles and page ids")]
+    async fn list_pages(
         &self,
-        #[tool(aggr)] AddPageRequest { title, content }: AddPageRequest,
+        #[tool(aggr)] ListPagesRequest { limit }: ListPagesRequest,
     ) -> Result<CallToolResult, McpError> {
-        // Split and format the content
-        let content_chunks = split_content(&content, MAX_BLOCK_SIZE);
-        let mut all_blocks = Vec::new();
-        
-        for chunk in content_chunks {
-            all_blocks.extend(format_for_notion(&chunk));
-        }
-        
-        match self.search_ref_db().await {
-            Ok(db_id) => {
-                // Use the new create_page_with_blocks method
-                match self.data_store.create_page_with_blocks(&db_id, &title, &all_blocks).await {
-                    Ok((_, json_resp)) => Ok(CallToolResult::success(vec![Content::text(
-                        json_resp.to_string(),
-                    )])),
-                    Err(e) => Err(McpError::internal_error(
-                        format!("error occurred: error creating page: {}", e),
-                        None,
-                    )),
+        let db_id = self.search_ref_db().await.map_err(|e| {
+            McpError::internal_error(
+                format!("error occurred: jotdown database not found, create a page first: {}", e),
+                None,
+            )
+        })?;
+        let max_scan = max_scan();
+        let requested = limit.unwrap_or(DEFAULT_LIST_LIMIT);
+        match self.data_store.query_database(&db_id, requested.min(max_scan)).await {
+            Ok((_, json_resp)) => {
+                let truncated = requested > max_scan && json_resp.get("has_more").and_then(|v| v.as_bool()).unwrap_or(false);
+                let pages: Vec<Value> = json_resp
+                    .get("results")
+                    .and_then(|v| v.as_array())
+                    .map(|results| {
+                        results
+                            .iter()
+                            .map(|page| json!({
+                                "title": page_title(page),
+                                "page_id": page.get("id").and_then(|v| v.as_str()).unwrap_or_default()
+                            }))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let mut message = format!("Listed {} pages", pages.len());
+                if truncated {
+                    message.push_str(&truncation_note(max_scan));
                 }
-            },
-            Err(_e) => {
-                if let Ok(page_id) = self.search_ref_page().await {
-                    match self.create_ref_db(page_id.as_str()).await {
-                        Ok(db_id) => {
-                            match self.data_store.create_page_with_blocks(&db_id, &title, &all_blocks).await {
-                                Ok((_, json_resp)) => Ok(CallToolResult::success(vec![Content::text(
-                                    json_resp.to_string(),
-                                )])),
-                                Err(e) => Err(McpError::internal_error(
-                                    format!("error occurred: error creating page: {}", e),
-                                    None,
-                                )),
-                            }
-                        }
-                        Err(e) => Err(McpError::internal_error(
-                            format!("error occurred: error creating database: {}", e),
-                            None,
-                        )),
+                Ok(tool_result(message, json!({ "results": pages, "truncated": truncated })))
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("error occurred: error listing pages: {}", e),
+                None,
+            )),
+        }
+    }
+
+    #[tool(description = "Get the properties of a database for given database id as a list of property names and types")]
+    async fn get_database_schema(&self, #[tool(param)] database_id: String) -> Result<CallToolResult, McpError> {
+        let database_id = page_id_param(&database_id)?;
+        match self.data_store.fetch_database(database_id.as_str()).await {
+            Ok((_, database)) => {
+                let properties = schema_properties(&database);
+                Ok(tool_result(
+                    format!("Database {} has {} properties", database_id, properties.len()),
+                    json!({ "database_id": database_id, "properties": properties }),
+                ))
+            }
+            Err(e) if e.downcast_ref::<NotionError>().is_some_and(|e| e.status == StatusCode::NOT_FOUND) => {
+                Err(McpError::resource_not_found(
+                    format!("error occurred: database {} not found or not shared with the integration", database_id),
+                    None,
+                ))
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("error occurred: error reading database schema: {}", e),
+                None,
+            )),
+        }
+    }
+
+    #[tool(description = "Export a page for given page id to a local markdown file, returning the file path")]
+    async fn export_page(
+        &self,
+        #[tool(aggr)] ExportPageRequest { page_id, path }: ExportPageRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let page_id = page_id_param(&page_id)?;
+        let title = match self.data_store.fetch_page(&page_id).await {
+            Ok((_, page)) => page_title(&page),
+            Err(e) => {
+                return Err(McpError::internal_error(
+                    format!("error occurred: error reading page: {}", e),
+                    None,
+                ));
+            }
+        };
+        let blocks = self.data_store.fetch_all_blocks(&page_id).await.map_err(|e| {
+            McpError::internal_error(format!("error occurred: error reading page: {}", e), None)
+        })?;
+
+        let written = book_root(env::var("JOTDOWN_BOOK_DIR").ok()).and_then(|default_dir| {
+            write_page_export(path.as_deref(), &default_dir, &title, &blocks_to_markdown(&blocks))
+        });
+        match written {
+            Ok(file_path) => Ok(tool_result(
+                format!("Page exported to: {}", file_path.display()),
+                json!({ "page_id": page_id, "path": file_path }),
+            )),
+            Err(e) => Err(McpError::internal_error(
+                format!("error occurred: error writing export: {}", e),
+                None,
+            )),
+        }
+    }
+
+    #[tool(description = "Add a page for given page id as a new chapter of an existing mdbook")]
+    async fn page_to_chapter(
+        &self,
+        #[tool(aggr)] PageToChapterRequest { page_id, book_path }: PageToChapterRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let page_id = page_id_param(&page_id)?;
+        let title = match self.data_store.fetch_page(&page_id).await {
+            Ok((_, page)) => page_title(&page),
+            Err(e) => {
+                return Err(McpError::internal_error(
+                    format!("error occurred: error reading page: {}", e),
+                    None,
+                ));
+            }
+        };
+        let blocks = self.data_store.fetch_all_blocks(&page_id).await.map_err(|e| {
+            McpError::internal_error(format!("error occurred: error reading page: {}", e), None)
+        })?;
+
+        let written = resolve_book_dir(&book_path)
+            .and_then(|book_dir| add_chapter(&book_dir, &title, &blocks_to_markdown(&blocks)));
+        match written {
+            Ok(chapter_path) => Ok(tool_result(
+                format!("Chapter written to: {} and linked in SUMMARY.md", chapter_path.display()),
+                json!({ "page_id": page_id, "path": chapter_path }),
+            )),
+            Err(e) => Err(McpError::internal_error(
+                format!("error occurred: error adding chapter: {}", e),
+                None,
+            )),
+        }
+    }
+
+    #[tool(description = "Regenerate SUMMARY.md of an existing mdbook from the chapter files in its src directory")]
+    async fn rebuild_summary(&self, #[tool(param)] book_path: String) -> Result<CallToolResult, McpError> {
+        match resolve_book_dir(&book_path).and_then(|book_dir| rebuild_summary(&book_dir)) {
+            Ok(chapters) => Ok(tool_result(
+                format!("SUMMARY.md rebuilt with {} chapters", chapters),
+                json!({ "chapters": chapters }),
+            )),
+            Err(e) => Err(McpError::internal_error(
+                format!("error occurred: error rebuilding SUMMARY.md: {}", e),
+                None,
+            )),
+        }
+    }
+
+    #[tool(description = "Import an existing mdbook at a given path into Notion, creating a page titled `Book — Chapter` for every chapter linked in SUMMARY.md")]
+    async fn import_mdbook(&self, #[tool(param)] book_path: String) -> Result<CallToolResult, McpError> {
+        let (book, chapters) = match resolve_book_dir(&book_path)
+            .and_then(|book_dir| Ok((book_title(&book_dir), read_book_chapters(&book_dir)?)))
+        {
+            Ok(book) => book,
+            Err(e) => {
+                return Err(McpError::invalid_params(
+                    format!("error occurred: error reading the mdbook at {}: {}", book_path, e),
+                    None,
+                ));
+            }
+        };
+        let db_id = match self.ref_db_id().await {
+            Ok(db_id) => db_id,
+            Err(e) => {
+                return Err(McpError::internal_error(
+                    format!("error occurred: {}", e),
+                    None,
+                ));
+            }
+        };
+
+        let (db_id, book) = (db_id.as_str(), book.as_str());
+        let max_size = max_content_size();
+        let results = run_bounded(chapters.into_iter().enumerate(), concurrency(), |(index, chapter)| async move {
+            let title = format!("{} — {}", book, chapter.title);
+            let mut result = json!({ "index": index, "title": title });
+            if let Err(e) = validate_content(&chapter.markdown, max_size) {
+                result["error"] = json!(e.message);
+                return result;
+            }
+            match self.data_store.create_page_with_blocks(db_id, &title, &content_blocks(&chapter.markdown), None, &[]).await {
+                Ok(creation) => {
+                    result["page_id"] = creation.response["id"].clone();
+                    if let Some(failure) = creation.incomplete {
+                        result["warning"] = json!(format!("the page is incomplete, {}", failure));
                     }
-                } else {
-                    Err(McpError::internal_error(
-                        "error occurred: ref page not found",
-                        None,
-                    ))
+                }
+                Err(e) => {
+                    self.invalidate_ref_db_on_not_found(&e);
+                    result["error"] = json!(e.to_string());
                 }
             }
+            result
+        })
+        .await;
+
+        let created = results.iter().filter(|result| result.get("page_id").is_some()).count();
+        Ok(tool_result(
+            format!("Imported {} of {} chapters of {}", created, results.len(), book),
+            json!({ "results": results }),
+        ))
+    }
+
+    #[tool(description = "Read a page for given page id and return its content as markdown")]
+    async fn read_page(&self, #[tool(param)] page_id: String) -> Result<CallToolResult, McpError> {
+        let page_id = page_id_param(&page_id)?;
+        match self.data_store.fetch_all_blocks(page_id.as_str()).await {
+            Ok(blocks) => Ok(tool_result(
+                format!("Read page {}", page_id),
+                json!({ "page_id": page_id, "markdown": blocks_to_markdown(&blocks) }),
+            )),
+            Err(e) => Err(McpError::internal_error(
+                format!("error occurred: error reading page: {}", e),
+                None,
+            )),
         }
     }
 
-    #[tool(description = "Create an mdbook for the given name and content")]
-    async fn create_mdbook(
+    #[tool(description = "Preview the Notion blocks markdown content would be converted to, grouped by content chunk, without creating anything in Notion")]
+    async fn preview_blocks(&self, #[tool(param)] content: String) -> Result<CallToolResult, McpError> {
+        let preview = preview_blocks(&content);
+        Ok(tool_result(format!("Content converts to {} blocks", preview["block_count"]), preview))
+    }
+
+    #[tool(description = "Get the word count, character count and number of blocks of a page for given page id")]
+    async fn page_stats(&self, #[tool(param)] page_id: String) -> Result<CallToolResult, McpError> {
+        let page_id = page_id_param(&page_id)?;
+        match self.data_store.fetch_all_blocks(page_id.as_str()).await {
+            Ok(blocks) => {
+                let mut stats = page_stats(&blocks);
+                stats["page_id"] = json!(page_id);
+                Ok(tool_result(format!("Stats of page {}", page_id), stats))
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("error occurred: error reading page: {}", e),
+                None,
+            )),
+        }
+    }
+
+    #[tool(description = "Append content to the end of a page for given content and page id, keeping the existing content")]
+    async fn append_to_page(&self, #[tool(aggr)] UpdatePageRequest { page_id, content }: UpdatePageRequest) -> Result<CallToolResult, McpError> {
+        let page_id = page_id_param(&page_id)?;
+        validate_content(&content, max_content_size())?;
+        self.append_content(&page_id, &content).await
+    }
+
+    #[tool(description = "Append content to the end of the page with the given title, without looking up its page id first. Fails when no page or several pages have that title")]
+    async fn append_by_title(
         &self,
-        #[tool(aggr)] AddMdBook { name, content}: AddMdBook,
+        #[tool(aggr)] AppendByTitleRequest { title, content }: AppendByTitleRequest,
     ) -> Result<CallToolResult, McpError> {
-      match self.bundle_mdbook(&name, content) {
-        Ok(path_buf) => {
-            Ok(CallToolResult::success(vec![Content::text(
-                format!("File created at: {}, now please run mdbook serve -o to serve it", path_buf.display()),
-            )]))
-        },
-        Err(e) => {
-            Err(McpError::internal_error(
-                format!("error occurred: create the mdbook operation failed: {}", e),
+        validate_title(&title)?;
+        validate_content(&content, max_content_size())?;
+        let json_resp = match self.data_store.search_pages(title.trim(), 100).await {
+            Ok((_, json_resp)) => json_resp,
+            Err(e) => {
+                return Err(McpError::internal_error(
+                    format!("error occurred: error searching pages: {}", e),
+                    None,
+                ));
+            }
+        };
+        let matches = pages_titled(&json_resp, &title);
+        match matches.as_slice() {
+            [] => Err(McpError::resource_not_found(
+                format!("error occurred: no page titled '{}' was found or shared with the integration", title.trim()),
                 None,
-            ))
+            )),
+            [page] => {
+                self.append_content(page["page_id"].as_str().unwrap_or_default(), &content).await
+            }
+            pages => {
+                let candidates: Vec<String> = pages
+                    .iter()
+                    .map(|page| format!("{} (last edited {})", page["page_id"].as_str().unwrap_or_default(), page["last_edited"].as_str().unwrap_or_default()))
+                    .collect();
+                Err(McpError::invalid_params(
+                    format!(
+                        "error occurred: {} pages are titled '{}': {}; use append_to_page with one of these page ids",
+                        pages.len(),
+                        title.trim(),
+                        candidates.join(", ")
+                    ),
+                    Some(json!({ "results": matches })),
+                ))
+            }
         }
-      }
     }
 
-    #[tool(description = "Serve mdbook from a given path")]
-    async fn serve_mdbook(&self, #[tool(param)] path: String) -> Result<CallToolResult, McpError> {
-        match self.open_mdbook(path.clone()).await {
+    #[tool(description = "Jot a single line of text to the Inbox page as a timestamped bullet, creating the page when missing. The fastest way to capture a note")]
+    async fn quick_note(&self, #[tool(param)] text: String) -> Result<CallToolResult, McpError> {
+        if text.trim().is_empty() {
+            return Err(McpError::invalid_params("error occurred: note text is empty", None));
+        }
+        validate_content(&text, max_content_size())?;
+        let page_id = self.inbox_page_id().await.map_err(|e| {
+            McpError::internal_error(format!("error occurred: error finding the inbox page: {}", e), None)
+        })?;
+        let blocks = quick_note_blocks(&text, Utc::now(), timezone_from(env::var("JOTDOWN_TZ").ok()));
+        match self.data_store.append_blocks(&page_id, &blocks).await {
+            Ok(_) => Ok(tool_result(
+                format!("Noted in {}: {}", INBOX_TITLE, text.trim()),
+                json!({ "page_id": page_id }),
+            )),
+            Err(e) => Err(McpError::internal_error(
+                format!("error occurred: error appending quick note: {}", e),
+                None,
+            )),
+        }
+    }
+
+    #[tool(description = "Alias of replace_page_content, kept for existing clients. Replaces the whole content of a page for given content and page id")]
+    async fn update_page(&self, #[tool(aggr)] request: UpdatePageRequest) -> Result<CallToolResult, McpError> {
+        self.replace_page_content(request).await
+    }
+
+    #[tool(description = "Replace the body of a page for given page id with new content, deleting every existing block first. update_page is an alias of this tool. Use append_to_page to add content instead")]
+    async fn replace_page_content(
+        &self,
+        #[tool(aggr)] UpdatePageRequest { page_id, content }: UpdatePageRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let page_id = page_id_param(&page_id)?;
+        validate_content(&content, max_content_size())?;
+        match self.data_store.replace_page_content(&page_id, &content_blocks(&content)).await {
+            Ok(replacement) => Ok(tool_result(
+                format!(
+                    "Page content replaced: deleted {} blocks, wrote {} blocks",
+                    replacement.deleted, replacement.written
+                ),
+                json!({ "page_id": page_id, "deleted": replacement.deleted, "written": replacement.written }),
+            )),
+            Err(e) => Err(McpError::internal_error(
+                format!("error occurred: error replacing page content: {}", e),
+                None,
+            )),
+        }
+    }
+
+    #[tool(description = "Move a page under another page for given page id and parent page id")]
+    async fn move_page(
+        &self,
+        #[tool(aggr)] MovePageRequest { page_id, parent_page_id }: MovePageRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let page_id = page_id_param(&page_id)?;
+        let parent_page_id = page_id_param(&parent_page_id)?;
+        match self.data_store.move_page(&page_id, &parent_page_id).await {
+            Ok(MoveOutcome::Moved) => Ok(tool_result(
+                format!("Page {} moved under page {}", page_id, parent_page_id),
+                json!({ "page_id": page_id }),
+            )),
+            Ok(MoveOutcome::Copied(copy)) => {
+                let title = page_title(&copy);
+                let mut fields = page_fields(&copy);
+                fields["original_page_id"] = json!(page_id);
+                Ok(tool_result(
+                    format!(
+                        "The Notion API can't change the parent of page {}, so it was copied under page {} \
+                         and the original archived. The copy has a new id and link; comments, page history \
+                         and database properties of the original were not carried over.\n{}",
+                        page_id,
+                        parent_page_id,
+                        created_page_message(&title, &copy),
+                    ),
+                    fields,
+                ))
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("error occurred: error moving page: {}", e),
+                None,
+            )),
+        }
+    }
+
+    #[tool(description = "Check that the Notion token works, returning the integration's bot name and workspace")]
+    async fn health_check(&self) -> Result<CallToolResult, McpError> {
+        match self.data_store.fetch_bot_user().await {
+            Ok((_, user)) => {
+                let name = user.get("name").and_then(|v| v.as_str()).unwrap_or("(unnamed)");
+                let workspace = user["bot"]["workspace_name"].as_str().unwrap_or("(unknown workspace)");
+                Ok(tool_result(
+                    format!("Notion token is valid: bot {} in workspace {}", name, workspace),
+                    json!({ "bot": name, "workspace": workspace }),
+                ))
+            }
+            Err(e) if e.downcast_ref::<NotionError>().is_some_and(|e| e.status == StatusCode::UNAUTHORIZED) => {
+                Err(McpError::invalid_request(
+                    "error occurred: Notion rejected the token (401 unauthorized), check NOTION_TOKEN and that the integration still exists",
+                    None,
+                ))
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("error occurred: error checking the Notion token: {}", e),
+                None,
+            )),
+        }
+    }
+
+    #[tool(description = "Rename a page for given page id and new title")]
+    async fn rename_page(
+        &self,
+        #[tool(aggr)] RenamePageRequest { page_id, new_title }: RenamePageRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let page_id = page_id_param(&page_id)?;
+        validate_title(&new_title)?;
+        match self.data_store.update_page_title(&page_id, &new_title).await {
+            Ok(_) => Ok(tool_result(
+                format!("Page {} renamed to: {}", page_id, new_title),
+                json!({ "page_id": page_id, "title": new_title }),
+            )),
+            Err(e) => Err(McpError::internal_error(
+                format!("error occurred: error renaming page: {}", e),
+                None,
+            )),
+        }
+    }
+
+    #[tool(description = "Set properties of a page, such as Status (select) or Date, for given page id and property name to value pairs")]
+    async fn set_page_properties(
+        &self,
+        #[tool(aggr)] SetPagePropertiesRequest { page_id, properties }: SetPagePropertiesRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let page_id = page_id_param(&page_id)?;
+        if properties.is_empty() {
+            return Err(McpError::invalid_params("error occurred: no properties to set", None));
+        }
+        match self.data_store.set_page_properties(&page_id, &properties).await {
             Ok(_) => {
-                Ok(CallToolResult ::success(vec![Content::text(
-                    format!("book served successfully"),
-                )]))
+                let names: Vec<&String> = properties.keys().collect();
+                Ok(tool_result(
+                    format!("Set {} properties of page {}", names.len(), page_id),
+                    json!({ "page_id": page_id, "properties": names }),
+                ))
+            }
+            Err(e) => match e.downcast_ref::<NotionError>() {
+                Some(notion_error) if notion_error.status == StatusCode::NOT_FOUND => Err(McpError::resource_not_found(
+                    format!("error occurred: page {} not found or not shared with the integration", page_id),
+                    None,
+                )),
+                Some(_) => Err(McpError::internal_error(
+                    format!("error occurred: error setting page properties: {}", e),
+                    None,
+                )),
+                // Values that don't fit the property types are rejected before any update
+                None => Err(McpError::invalid_params(
+                    format!("error occurred: error setting page properties: {}", e),
+                    None,
+                )),
             },
+        }
+    }
+
+    #[tool(description = "Delete a page for given page id. Notion can't delete pages permanently, so this archives \
+                         (trashes) the page; it can be restored with unarchive_page")]
+    async fn delete_page(&self, #[tool(param)] page_id: String) -> Result<CallToolResult, McpError> {
+        self.set_page_archived(page_id, true).await
+    }
+
+    #[tool(description = "Archive a page for given page id, moving it to the Notion trash. Reversible with unarchive_page")]
+    async fn archive_page(&self, #[tool(param)] page_id: String) -> Result<CallToolResult, McpError> {
+        self.set_page_archived(page_id, true).await
+    }
+
+    #[tool(description = "Restore an archived (trashed or deleted) page for given page id")]
+    async fn unarchive_page(&self, #[tool(param)] page_id: String) -> Result<CallToolResult, McpError> {
+        self.set_page_archived(page_id, false).await
+    }
+
+    async fn set_page_archived(&self, page_id: String, archived: bool) -> Result<CallToolResult, McpError> {
+        let page_id = page_id_param(&page_id)?;
+        let result = if archived {
+            self.data_store.archive_page(page_id.as_str()).await
+        } else {
+            self.data_store.unarchive_page(page_id.as_str()).await
+        };
+        let (action, failure) = if archived {
+            ("archived", "archiving")
+        } else {
+            ("restored", "restoring")
+        };
+        match result {
+            Ok(_) => Ok(tool_result(
+                format!("Page {}: {}", action, page_id),
+                json!({ "page_id": page_id, "archived": archived }),
+            )),
+            Err(e) if e.downcast_ref::<NotionError>().is_some_and(|e| e.status == StatusCode::NOT_FOUND) => {
+                Err(McpError::resource_not_found(
+                    format!("error occurred: page {} not found or not shared with the integration", page_id),
+                    None,
+                ))
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("error occurred: error {} page: {}", failure, e),
+                None,
+            )),
+        }
+    }
+
+    #[tool(description = "Delete a single block (paragraph, heading, list item...) by its block id or block link")]
+    async fn delete_block(&self, #[tool(param)] block_id: String) -> Result<CallToolResult, McpError> {
+        let block_id = block_id_param(&block_id)?;
+        match self.data_store.delete_block(block_id.as_str()).await {
+            Ok(_) => Ok(tool_result(format!("Block deleted: {}", block_id), json!({ "block_id": block_id }))),
+            Err(e) if e.downcast_ref::<NotionError>().is_some_and(|e| e.status == StatusCode::NOT_FOUND) => {
+                Err(McpError::resource_not_found(
+                    format!("error occurred: block {} not found or not shared with the integration", block_id),
+                    None,
+                ))
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("error occurred: error deleting block: {}", e),
+                None,
+            )),
+        }
+    }
+
+    #[tool(description = "Create a new page")]
+    async fn create_new_page(
+        &self,
+        #[tool(aggr)] AddPageRequest { title, content, cover_url }: AddPageRequest,
+    ) -> Result<CallToolResult, McpError> {
+        validate_content(&content, max_content_size())?;
+        let (frontmatter, content) = split_frontmatter(&content);
+        let title = page_title_or(title, &frontmatter);
+        validate_title(&title)?;
+        let all_blocks = content_blocks(content);
+        
+        let db_id = match self.ref_db_id().await {
+            Ok(db_id) => db_id,
+            Err(e) => {
+                return Err(McpError::internal_error(
+                    format!("error occurred: {}", e),
+                    None,
+                ));
+            }
+        };
+
+        let cover = cover_from(cover_url.as_deref());
+        match self.data_store.create_page_with_blocks(&db_id, &title, &all_blocks, cover.ok().flatten(), &frontmatter.tags).await {
+            Ok(creation) => {
+                let mut message = page_creation_message(&title, &creation);
+                if let Err(ignored) = cover {
+                    message.push_str(&format!("\nWarning: cover_url '{}' is not an http(s) URL and was ignored.", ignored));
+                }
+                if all_blocks.is_empty() {
+                    message.push_str("\nWarning: the content was empty, the page was created without any blocks.");
+                }
+                Ok(tool_result(message, page_fields(&creation.response)))
+            }
             Err(e) => {
+                self.invalidate_ref_db_on_not_found(&e);
                 Err(McpError::internal_error(
-                    format!("error occurred: {}, since we are facing the issue ask user to run mdbook serve -o in the path {} to serve the book", e, path.clone()),
+                    format!("error occurred: error creating page: {}", e),
                     None,
                 ))
             }
         }
     }
-}
 
-#[tool(tool_box)]
-impl ServerHandler for Jotter {
-    fn get_info(&self) -> ServerInfo {
-        ServerInfo {
-            protocol_version: ProtocolVersion::V_2024_11_05,
-            capabilities: ServerCapabilities::builder()
-                .enable_tools()
-                .build(),
-            server_info: Implementation::from_build_env(),
-            instructions: Some("This server provides a tool to jot down your notes to Notion. You can create new pages on Notion or also bundle those notes into a mdbook".to_string()),
+    #[tool(description = "Create a new page directly under another page for given parent page id, instead of in the Jotdown database")]
+    async fn create_subpage(
+        &self,
+        #[tool(aggr)] CreateSubpageRequest { parent_page_id, title, content }: CreateSubpageRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let parent_page_id = page_id_param(&parent_page_id)?;
+        validate_title(&title)?;
+        validate_content(&content, max_content_size())?;
+        match self.data_store.create_child_page_with_blocks(&parent_page_id, &title, &content_blocks(&content)).await {
+            Ok(creation) => Ok(tool_result(
+                page_creation_message(&title, &creation),
+                page_fields(&creation.response),
+            )),
+            Err(e) if e.downcast_ref::<NotionError>().is_some_and(|e| e.status == StatusCode::NOT_FOUND) => {
+                Err(McpError::resource_not_found(
+                    format!("error occurred: page {} not found or not shared with the integration", parent_page_id),
+                    None,
+                ))
+            }
+            Err(e) => Err(McpError::internal_error(
+                format!("error occurred: error creating page: {}", e),
+                None,
+            )),
         }
     }
+
+    #[tool(description = "Create several pages at once, returning the page id or error of each one in order")]
+    async fn bulk_create_pages(
+        &self,
+        #[tool(aggr)] BulkCreatePagesRequest { pages }: BulkCreatePagesRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let db_id = match self.ref_db_id().await {
+            Ok(db_id) => db_id,
+            Err(e) => {
+                return Err(McpError::internal_error(
+                    format!("error occurred: {}", e),
+                    None,
+                ));
+            }
+        };
+
+        let db_id = db_id.as_str();
+        let max_size = max_content_size();
+        let results = run_bounded(pages.into_iter().enumerate(), concurrency(), |(index, page)| async move {
+            let (frontmatter, content) = split_frontmatter(&page.content);
+            let title = page_title_or(page.title, &frontmatter);
+            let mut result = json!({ "index": index, "title": title });
+            let mut warnings = Vec::new();
+            if title.trim().is_empty() {
+                result["error"] = json!("title is empty");
+                return result;
+            }
+            if let Err(e) = validate_content(&page.content, max_size) {
+                result["error"] = json!(e.message);
+                return result;
+            }
+            let cover = cover_from(page.cover_url.as_deref());
+            if let Err(ignored) = cover {
+                warnings.push(format!("cover_url '{}' is not an http(s) URL and was ignored", ignored));
+            }
+            match self.data_store.create_page_with_blocks(db_id, &title, &content_blocks(content), cover.ok().flatten(), &frontmatter.tags).await {
+                Ok(creation) => {
+                    result["page_id"] = creation.response["id"].clone();
+                    if let Some(url) = creation.response.get("url") {
+                        result["url"] = url.clone();
+                    }
+                    if let Some(failure) = creation.incomplete {
+                        warnings.push(format!("the page is incomplete, {}", failure));
+                    }
+                }
+                Err(e) => {
+                    self.invalidate_ref_db_on_not_found(&e);
+                    result["error"] = json!(e.to_string());
+                }
+            }
+            if !warnings.is_empty() {
+                result["warnings"] = json!(warnings);
+            }
+            result
+        })
+        .await;
+
+        let created = results.iter().filter(|result| result.get("page_id").is_some()).count();
+        Ok(tool_result(
+            format!("Created {} of {} pages", created, results.len()),
+            json!({ "results": results }),
+        ))
+    }
+
+    #[tool(description = "Duplicate a page into a new page with the given title, copying all of its content")]
+    async fn duplicate_page(
+        &self,
+        #[tool(aggr)] DuplicatePageRequest { page_id, title }: DuplicatePageRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let page_id = page_id_param(&page_id)?;
+        validate_title(&title)?;
+        let db_id = match self.ref_db_id().await {
+            Ok(db_id) => db_id,
+            Err(e) => {
+                return Err(McpError::internal_error(
+                    format!("error occurred: {}", e),
+                    None,
+                ));
+            }
+        };
+
+        match self.data_store.duplicate_page(&page_id, &db_id, &title).await {
+            Ok(creation) => Ok(tool_result(
+                page_creation_message(&title, &creation),
+                page_fields(&creation.response),
+            )),
+            Err(e) => {
+                self.invalidate_ref_db_on_not_found(&e);
+                Err(McpError::internal_error(
+                    format!("error occurred: error duplicating page: {}", e),
+                    None,
+                ))
+            }
+        }
+    }
+
+    #[tool(description = "Create an mdbook for the given name and content")]
+    async fn create_mdbook(
+        &self,
+        #[tool(aggr)] AddMdBook { name, content, output_dir }: AddMdBook,
+    ) -> Result<CallToolResult, McpError> {
+      ensure_mdbook_installed(MDBOOK_BINARY)?;
+      match self.bundle_mdbook(&name, content, output_dir.as_deref()) {
+        Ok(path_buf) => {
+            let url = directory_url(&path_buf);
+            Ok(tool_result(
+                format!(
+                    "File created at: {} ({}), now please run mdbook serve -o to serve it",
+                    path_buf.display(),
+                    url.as_deref().unwrap_or("no file URL for a relative path")
+                ),
+                json!({ "path": path_buf, "url": url }),
+            ))
+        },
+        Err(e) => {
+            Err(McpError::internal_error(
+                format!("error occurred: create the mdbook operation failed: {}", e),
+                None,
+            ))
+        }
+      }
+    }
+
+    #[tool(description = "Serve mdbook from a given path in the background and return the URL it is served at")]
+    async fn serve_mdbook(&self, #[tool(param)] path: String) -> Result<CallToolResult, McpError> {
+        ensure_mdbook_installed(MDBOOK_BINARY)?;
+        match self.open_mdbook(path.clone()).await {
+            Ok(url) => {
+                Ok(tool_result(format!("book served successfully at {}", url), json!({ "url": url })))
+            },
+            Err(e) => {
+                Err(McpError::internal_error(
+                    format!("error occurred: {}, since we are facing the issue ask user to run mdbook serve -o in the path {} to serve the book", e, path.clone()),
+                    None,
+                ))
+            }
+        }
+    }
+
+    #[tool(description = "Build the static HTML site of the mdbook at a given path without serving it, returning the output book directory")]
+    async fn build_mdbook(&self, #[tool(param)] path: String) -> Result<CallToolResult, McpError> {
+        ensure_mdbook_installed(MDBOOK_BINARY)?;
+        let built = match resolve_book_dir(&path) {
+            Ok(book_dir) => tokio::task::spawn_blocking(move || build_mdbook_site(MDBOOK_BINARY, &book_dir))
+                .await
+                .unwrap_or_else(|e| Err(e.into())),
+            Err(e) => Err(e),
+        };
+        match built {
+            Ok(output_dir) => Ok(tool_result(
+                format!("book built at {}", output_dir.display()),
+                json!({ "path": output_dir }),
+            )),
+            Err(e) => Err(McpError::internal_error(
+                format!("error occurred: error building the mdbook at {}: {}", path, e),
+                None,
+            )),
+        }
+    }
+
+    #[tool(description = "Stop the mdbook server running for a given path")]
+    async fn stop_mdbook(&self, #[tool(param)] path: String) -> Result<CallToolResult, McpError> {
+        match self.close_mdbook(&path) {
+            Ok(Some(pid)) => Ok(tool_result(
+                format!("stopped the mdbook server for {} (pid {})", path, pid),
+                json!({ "pid": pid }),
+            )),
+            Ok(None) => Ok(tool_result(format!("no server running for {}", path), json!({}))),
+            Err(e) => Err(McpError::internal_error(
+                format!("error occurred: error stopping the mdbook server for {}: {}", path, e),
+                None,
+            )),
+        }
+    }
+}
+
+/// Random version 4 UUID identifying a single tool invocation in the logs
+fn new_request_id() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+impl ServerHandler for Jotter {
+    async fn list_tools(
+        &self,
+        _: PaginatedRequestParam,
+        _: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        Ok(ListToolsResult {
+            next_cursor: None,
+            tools: Self::tool_box().list(),
+        })
+    }
+
+    /// Run a tool inside a span carrying a fresh request id, so every Notion
+    /// call it makes can be traced back to the invocation
+    ///
+    /// Tool errors are answered as `{"ok": false}` results rather than
+    /// protocol errors, matching the JSON shape of successful calls. Calls
+    /// that never reach a tool (unknown name, bad arguments) stay protocol errors.
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let span = tracing::info_span!("tool", request_id = %new_request_id(), name = %request.name);
+        async {
+            tracing::info!("tool invoked");
+            let context = ToolCallContext::new(self, request, context);
+            Self::tool_box().call(context).await.or_else(|e| {
+                if is_dispatch_error(&e) {
+                    tracing::warn!("tool call rejected: {}", e.message);
+                    return Err(e);
+                }
+                tracing::warn!("tool failed: {}", e.message);
+                Ok(failed_tool_result(&e))
+            })
+        }
+        .instrument(span)
+        .await
+    }
+
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            protocol_version: ProtocolVersion::V_2024_11_05,
+            capabilities: ServerCapabilities::builder()
+                .enable_tools()
+                .build(),
+            server_info: Implementation::from_build_env(),
+            instructions: Some("This server provides a tool to jot down your notes to Notion. You can create new pages on Notion or also bundle those notes into a mdbook".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{MockResponse, MockServer, RecordedRequest};
+
+    #[test]
+    fn test_new_request_id_is_uuid_v4() {
+        let id = new_request_id();
+        let groups: Vec<&str> = id.split('-').collect();
+        assert_eq!(groups.iter().map(|g| g.len()).collect::<Vec<_>>(), vec![8, 4, 4, 4, 12]);
+        assert!(groups[2].starts_with('4'));
+        assert!(matches!(groups[3].chars().next(), Some('8' | '9' | 'a' | 'b')));
+        assert_ne!(id, new_request_id());
+    }
+
+    /// Parse the JSON object a tool answered with
+    fn result_json(result: &CallToolResult) -> Value {
+        serde_json::from_str(&result.content[0].as_text().unwrap().text).unwrap()
+    }
+
+    #[test]
+    fn test_tool_results_share_one_shape() {
+        let response = json!({ "object": "page", "id": "page-1", "url": "https://www.notion.so/page-1" });
+        let result = tool_result("Created page: Notes", page_fields(&response));
+        assert_eq!(result.is_error, Some(false));
+        assert_eq!(result_json(&result), json!({
+            "ok": true,
+            "message": "Created page: Notes",
+            "page_id": "page-1",
+            "url": "https://www.notion.so/page-1"
+        }));
+        assert_eq!(page_fields(&json!({ "id": "page-2" })), json!({ "page_id": "page-2" }));
+
+        let failed = failed_tool_result(&McpError::internal_error("error occurred: boom", None));
+        assert_eq!(failed.is_error, Some(true));
+        assert_eq!(result_json(&failed), json!({ "ok": false, "message": "error occurred: boom" }));
+    }
+
+    #[test]
+    fn test_failed_tool_result_keeps_error_data() {
+        let error = McpError::invalid_params(
+            "error occurred: 2 pages are titled 'Notes'",
+            Some(json!({ "results": [{ "page_id": "page-1" }, { "page_id": "page-2" }], "ok": true })),
+        );
+        assert_eq!(result_json(&failed_tool_result(&error)), json!({
+            "ok": false,
+            "message": "error occurred: 2 pages are titled 'Notes'",
+            "results": [{ "page_id": "page-1" }, { "page_id": "page-2" }]
+        }));
+
+        let error = McpError::internal_error("error occurred: boom", Some(json!("details")));
+        assert_eq!(result_json(&failed_tool_result(&error))["data"], "details");
+    }
+
+    #[test]
+    fn test_dispatch_errors_stay_protocol_errors() {
+        assert!(is_dispatch_error(&McpError::invalid_params("tool not found", None)));
+        assert!(is_dispatch_error(&McpError::invalid_params("missing parameter page_id", None)));
+        assert!(is_dispatch_error(&McpError::invalid_params("failed to deserialize parameters: missing field `title`", None)));
+        assert!(is_dispatch_error(&McpError::method_not_found::<CallToolRequestMethod>()));
+        assert!(!is_dispatch_error(&McpError::invalid_params("error occurred: title is empty", None)));
+        assert!(!is_dispatch_error(&McpError::internal_error("failed to deserialize parameters", None)));
+    }
+
+    #[test]
+    fn test_page_title_reads_title_property() {
+        let page = json!({
+            "id": "page-1",
+            "properties": {
+                "Content": { "type": "rich_text", "rich_text": [] },
+                "Name": {
+                    "type": "title",
+                    "title": [
+                        { "plain_text": "Grocery " },
+                        { "plain_text": "list" }
+                    ]
+                }
+            }
+        });
+        assert_eq!(page_title(&page), "Grocery list");
+        assert_eq!(page_title(&json!({})), "");
+    }
+
+    #[test]
+    fn test_created_page_message_from_response() {
+        let response = json!({
+            "object": "page",
+            "id": "1a2b3c4d-0000-0000-0000-000000000000",
+            "created_time": "2024-01-01T00:00:00.000Z",
+            "url": "https://www.notion.so/Groceries-1a2b3c4d000000000000000000000000",
+            "properties": {}
+        });
+        assert_eq!(
+            created_page_message("Groceries", &response),
+            "Created page: Groceries — https://www.notion.so/Groceries-1a2b3c4d000000000000000000000000"
+        );
+        assert_eq!(
+            created_page_message("Groceries", &json!({ "id": "page-1" })),
+            "Created page: Groceries"
+        );
+    }
+
+    #[test]
+    fn test_preview_blocks_groups_blocks_by_chunk() {
+        let preview = preview_blocks("# Title\n- item\n\nSome text");
+        assert_eq!(preview["block_count"], 4);
+        let chunks = preview["chunks"].as_array().unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0]["chunk"], 0);
+        assert_eq!(chunks[0]["chars"], 25);
+        assert_eq!(chunks[0]["blocks"][0]["type"], "heading_1");
+        assert_eq!(chunks[0]["blocks"][1]["type"], "bulleted_list_item");
+
+        let long = "word ".repeat(1000);
+        let preview = preview_blocks(&long);
+        assert!(preview["chunks"].as_array().unwrap().len() > 1);
+    }
+
+    #[test]
+    fn test_content_blocks_footnote() {
+        let blocks = content_blocks("Water boils at 100°C[^1] at sea level.\n\n[^1]: At 1 atm of pressure.");
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(
+            blocks[0]["paragraph"]["rich_text"][0]["text"]["content"],
+            "Water boils at 100°C[1] at sea level."
+        );
+        assert_eq!(blocks[1]["type"], "divider");
+        assert_eq!(blocks[2]["type"], "numbered_list_item");
+        assert_eq!(blocks[2]["numbered_list_item"]["rich_text"][0]["text"]["content"], "At 1 atm of pressure.");
+    }
+
+    #[test]
+    fn test_preview_blocks_resolves_footnote_across_chunks() {
+        let content = format!("# Intro\nSee the note[^1].\n# Body\n{}\n# End\n[^1]: Defined far away.", "word ".repeat(500));
+        assert!(split_content(&content, max_block_size()).len() > 1);
+
+        let preview = preview_blocks(&content);
+
+        let chunks = preview["chunks"].as_array().unwrap();
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks[0]["blocks"][1]["paragraph"]["rich_text"][0]["text"]["content"], "See the note[1].");
+        assert!(!preview["chunks"].to_string().contains("[^1]"));
+        let notes = preview["footnotes"].as_array().unwrap();
+        assert_eq!(notes[1]["numbered_list_item"]["rich_text"][0]["text"]["content"], "Defined far away.");
+        assert_eq!(preview["block_count"].as_u64().unwrap() as usize, content_blocks(&content).len());
+    }
+
+    #[test]
+    fn test_page_stats_counts_nested_blocks() {
+        let blocks = json!([
+            {
+                "type": "paragraph",
+                "paragraph": {
+                    "rich_text": [{ "plain_text": "Hello world" }],
+                    "children": [
+                        { "type": "bulleted_list_item", "bulleted_list_item": { "rich_text": [{ "plain_text": "one two" }] } }
+                    ]
+                }
+            },
+            { "type": "heading_1", "heading_1": { "rich_text": [{ "plain_text": "Title" }] } }
+        ]);
+        let stats = page_stats(blocks.as_array().unwrap());
+        assert_eq!(stats, json!({ "words": 5, "chars": 25, "blocks": 3 }));
+    }
+
+    fn temp_book_dir(test_name: &str) -> std::path::PathBuf {
+        let dir = env::temp_dir().join(format!("jotdown-{}-{}", test_name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_write_mdbook_uses_title() {
+        let dir = temp_book_dir("book-title");
+        let chapters = vec![MdBookChapter { name: "Start".to_string(), content: "# Start".to_string() }];
+        write_mdbook(&dir, "Rust \"Notes\"", &chapters).unwrap();
+
+        let book_toml = fs::read_to_string(dir.join("book.toml")).unwrap();
+        assert!(book_toml.contains("[book]"));
+        assert!(book_toml.contains("title = \"Rust \\\"Notes\\\"\""));
+        let readme = fs::read_to_string(dir.join("src/README.md")).unwrap();
+        assert!(readme.starts_with("# Rust \"Notes\""));
+        let summary = fs::read_to_string(dir.join("src/SUMMARY.md")).unwrap();
+        assert!(summary.contains("* [Start](start.md)"));
+        assert_eq!(fs::read_to_string(dir.join("src/start.md")).unwrap(), "# Start");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_timezone_from_falls_back_to_utc() {
+        assert_eq!(timezone_from(None), Tz::UTC);
+        assert_eq!(timezone_from(Some("Not/AZone".to_string())), Tz::UTC);
+        assert_eq!(timezone_from(Some(" Asia/Kolkata ".to_string())), Tz::Asia__Kolkata);
+
+        let time = DateTime::parse_from_rfc3339("2024-01-15T12:00:00Z").unwrap().with_timezone(&Utc);
+        assert_eq!(format_timestamp(time, timezone_from(Some("Not/AZone".to_string()))), "2024-01-15 12:00 UTC");
+        assert_eq!(format_timestamp(time, Tz::Asia__Kolkata), "2024-01-15 17:30 IST");
+    }
+
+    #[test]
+    fn test_write_page_export() {
+        let dir = temp_book_dir("export");
+        fs::create_dir_all(&dir).unwrap();
+
+        let written = write_page_export(None, &dir, "Trip: Plans!", "- pack\n- go").unwrap();
+        assert_eq!(written, fs::canonicalize(dir.join("trip-plans.md")).unwrap());
+        assert_eq!(fs::read_to_string(&written).unwrap(), "# Trip: Plans!\n\n- pack\n- go\n");
+
+        let custom = dir.join("nested/backup.md");
+        let written = write_page_export(custom.to_str(), &dir, "Ignored", "text").unwrap();
+        assert!(written.ends_with("nested/backup.md"));
+
+        let written = write_page_export(dir.to_str(), &dir, "", "text").unwrap();
+        assert!(written.ends_with("page.md"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_add_chapter_links_it_in_summary() {
+        let dir = temp_book_dir("add-chapter");
+        let chapters = vec![MdBookChapter { name: "Meeting Notes".to_string(), content: "old".to_string() }];
+        write_mdbook(&dir, "Book", &chapters).unwrap();
+
+        let path = add_chapter(&dir, "Meeting Notes", "- decided things").unwrap();
+
+        assert_eq!(path, dir.join("src/meeting-notes-2.md"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "# Meeting Notes\n\n- decided things\n");
+        let summary = fs::read_to_string(dir.join("src/SUMMARY.md")).unwrap();
+        assert!(summary.ends_with("* [Meeting Notes](meeting-notes.md)\n* [Meeting Notes](meeting-notes-2.md)\n"));
+        assert!(add_chapter(&dir.join("src"), "Other", "").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sanitize_book_name_rejects_parent_dir() {
+        assert!(sanitize_book_name("../evil").is_err());
+        assert!(sanitize_book_name("notes/../../evil").is_err());
+    }
+
+    #[test]
+    fn test_sanitize_book_name_replaces_separators_and_spaces() {
+        assert_eq!(sanitize_book_name("my/book").unwrap(), "my-book");
+        assert_eq!(sanitize_book_name("  My Rust  Book ").unwrap(), "My-Rust-Book");
+        assert_eq!(sanitize_book_name("C:\\notes\\book").unwrap(), "C-notes-book");
+        assert_eq!(sanitize_book_name(".hidden").unwrap(), "hidden");
+    }
+
+    #[test]
+    fn test_sanitize_book_name_rejects_empty() {
+        assert!(sanitize_book_name("").is_err());
+        assert!(sanitize_book_name(" / ").is_err());
+    }
+
+    #[test]
+    fn test_book_root_honors_custom_dir() {
+        let root = env::temp_dir().join("jotdown-custom-root");
+        let custom = book_root(Some(root.display().to_string())).unwrap();
+        assert_eq!(custom, root);
+        assert_eq!(custom.join(sanitize_book_name("My Book").unwrap()), root.join("My-Book"));
+    }
+
+    #[test]
+    fn test_rebuild_summary_links_chapter_files() {
+        let dir = temp_book_dir("rebuild-summary");
+        let chapters = vec![MdBookChapter { name: "Setup".to_string(), content: "# Setup guide\n\nsteps".to_string() }];
+        write_mdbook(&dir, "Book", &chapters).unwrap();
+        // Added by hand, without a heading
+        fs::write(dir.join("src").join("appendix.md"), "extra notes\n").unwrap();
+
+        assert_eq!(rebuild_summary(&dir).unwrap(), 2);
+
+        let summary = fs::read_to_string(dir.join("src").join("SUMMARY.md")).unwrap();
+        assert_eq!(
+            summary,
+            "# Summary\n* [Introduction](README.md)\n* [Setup guide](setup.md)\n* [appendix](appendix.md)\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(rebuild_summary(&dir).is_err());
+    }
+
+    #[test]
+    fn test_write_atomically_leaves_nothing_on_failure() {
+        let root = temp_book_dir("atomic-failure");
+        fs::create_dir_all(&root).unwrap();
+        let book = root.join("Book");
+
+        let err = write_atomically(&book, |dir| {
+            let chapters = vec![MdBookChapter { name: "One".to_string(), content: "# One".to_string() }];
+            write_mdbook(dir, "Book", &chapters)?;
+            Err("disk full while writing chapter 2".into())
+        })
+        .unwrap_err();
+
+        assert_eq!(err.to_string(), "disk full while writing chapter 2");
+        assert!(!book.exists());
+        assert!(fs::read_dir(&root).unwrap().next().is_none());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_write_atomically_replaces_existing_book() {
+        let root = temp_book_dir("atomic-replace");
+        let book = root.join("Book");
+        fs::create_dir_all(book.join("src")).unwrap();
+        fs::write(book.join("src").join("old.md"), "# Old").unwrap();
+
+        // A failed rewrite keeps the previous book
+        assert!(write_atomically(&book, |_| Err("boom".into())).is_err());
+        assert!(book.join("src").join("old.md").exists());
+
+        let chapters = vec![MdBookChapter { name: "New".to_string(), content: "# New".to_string() }];
+        write_atomically(&book, |dir| write_mdbook(dir, "Book", &chapters)).unwrap();
+        assert!(!book.join("src").join("old.md").exists());
+        assert!(book.join("src").join("SUMMARY.md").exists());
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 1);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_output_root_uses_absolute_output_dir() {
+        let dir = temp_book_dir("output-root");
+        assert_eq!(output_root(Some(&dir.display().to_string())).unwrap(), dir);
+        assert!(dir.is_dir());
+        assert!(fs::read_dir(&dir).unwrap().next().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_output_root_rejects_unwritable_dir() {
+        // A directory can't be created beneath a regular file, even as root
+        let file = temp_book_dir("output-root-file");
+        fs::write(&file, "not a directory").unwrap();
+        let err = output_root(Some(&file.join("book").display().to_string())).unwrap_err().to_string();
+        assert!(err.contains("is not writable"), "{}", err);
+        assert!(err.contains("choose another output_dir"), "{}", err);
+        fs::remove_file(&file).unwrap();
+
+        let err = output_root(Some("relative/books")).unwrap_err().to_string();
+        assert_eq!(err, "output_dir 'relative/books' must be an absolute path");
+    }
+
+    #[test]
+    fn test_book_root_defaults_to_home() {
+        assert_eq!(book_root(None).ok(), dirs::home_dir());
+        assert_eq!(book_root(Some("  ".to_string())).ok(), dirs::home_dir());
+    }
+
+    #[test]
+    fn test_chapter_filenames_from_names() {
+        let chapters = vec![
+            MdBookChapter { name: "Getting Started!".to_string(), content: String::new() },
+            MdBookChapter { name: "Ownership & Borrowing".to_string(), content: String::new() },
+        ];
+        assert_eq!(chapter_filenames(&chapters), vec!["getting-started.md", "ownership-borrowing.md"]);
+    }
+
+    #[test]
+    fn test_chapter_filenames_collisions() {
+        let chapters = vec![
+            MdBookChapter { name: "Intro".to_string(), content: String::new() },
+            MdBookChapter { name: "intro".to_string(), content: String::new() },
+            MdBookChapter { name: "INTRO?".to_string(), content: String::new() },
+            MdBookChapter { name: "Summary".to_string(), content: String::new() },
+            MdBookChapter { name: "???".to_string(), content: String::new() },
+        ];
+        assert_eq!(
+            chapter_filenames(&chapters),
+            vec!["intro.md", "intro-2.md", "intro-3.md", "summary-2.md", "chapter.md"]
+        );
+    }
+
+    #[test]
+    fn test_serving_url_from_mdbook_log() {
+        let line = "2024-05-01 10:00:00 [INFO] (mdbook::cmd::serve): Serving on: http://localhost:3456";
+        assert_eq!(serving_url(line).as_deref(), Some("http://localhost:3456"));
+        assert_eq!(serving_url("[INFO] (mdbook::book): Book building has started"), None);
+    }
+
+    #[test]
+    fn test_schema_properties_lists_names_and_types() {
+        let database = json!({
+            "object": "database",
+            "id": "db-1",
+            "title": [{ "plain_text": "Reading list" }],
+            "properties": {
+                "Title": { "id": "title", "name": "Title", "type": "title", "title": {} },
+                "Tags": { "id": "a1", "name": "Tags", "type": "multi_select", "multi_select": { "options": [] } },
+                "Due": { "id": "b2", "name": "Due", "type": "date", "date": {} }
+            }
+        });
+
+        assert_eq!(schema_properties(&database), vec![
+            json!({ "name": "Due", "type": "date" }),
+            json!({ "name": "Tags", "type": "multi_select" }),
+            json!({ "name": "Title", "type": "title" }),
+        ]);
+        assert!(schema_properties(&json!({ "object": "database" })).is_empty());
+    }
+
+    #[test]
+    fn test_search_results_lists_every_match() {
+        let response = json!({
+            "object": "list",
+            "results": [
+                {
+                    "object": "page",
+                    "id": "page-1",
+                    "last_edited_time": "2024-03-02T10:00:00.000Z",
+                    "properties": { "Name": { "type": "title", "title": [{ "plain_text": "Meeting notes" }] } }
+                },
+                {
+                    "object": "page",
+                    "id": "page-2",
+                    "last_edited_time": "2024-03-01T09:30:00.000Z",
+                    "properties": { "title": { "type": "title", "title": [{ "plain_text": "Meeting agenda" }] } }
+                }
+            ],
+            "has_more": false
+        });
+
+        assert_eq!(search_results(&response), vec![
+            json!({ "title": "Meeting notes", "page_id": "page-1", "last_edited": "2024-03-02T10:00:00.000Z" }),
+            json!({ "title": "Meeting agenda", "page_id": "page-2", "last_edited": "2024-03-01T09:30:00.000Z" }),
+        ]);
+        assert!(search_results(&json!({ "results": [] })).is_empty());
+    }
+
+    #[test]
+    fn test_cover_from_accepts_only_http_urls() {
+        assert_eq!(cover_from(None), Ok(None));
+        assert_eq!(cover_from(Some("  ")), Ok(None));
+        assert_eq!(cover_from(Some(" https://example.com/a.png ")), Ok(Some("https://example.com/a.png")));
+        assert_eq!(cover_from(Some("ftp://example.com/a.png")), Err("ftp://example.com/a.png"));
+        assert_eq!(cover_from(Some("https://example.com/a b.png")), Err("https://example.com/a b.png"));
+    }
+
+    #[test]
+    fn test_max_content_size_from_env_value() {
+        assert_eq!(max_content_size_from(None), DEFAULT_MAX_CONTENT_SIZE);
+        assert_eq!(max_content_size_from(Some(" 5000 ".to_string())), 5000);
+        assert_eq!(max_content_size_from(Some("0".to_string())), DEFAULT_MAX_CONTENT_SIZE);
+        assert_eq!(max_content_size_from(Some("lots".to_string())), DEFAULT_MAX_CONTENT_SIZE);
+    }
+
+    #[tokio::test]
+    async fn test_create_new_page_rejects_empty_title() {
+        let server = MockServer::start(vec![MockResponse::json(200, json!({ "object": "list", "results": [] }))]).await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+
+        let err = jotter
+            .create_new_page(AddPageRequest { title: "  ".to_string(), content: "note".to_string(), cover_url: None })
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+        assert!(err.message.contains("title is empty"));
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn test_validate_content_rejects_oversized_content() {
+        assert!(validate_content("héllo", 5).is_ok());
+        let err = validate_content("héllo!", 5).unwrap_err();
+        assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+        assert!(err.message.contains("content is 6 characters, more than the 5 allowed"));
+        assert!(err.message.contains("JOTDOWN_MAX_CONTENT_SIZE"));
+    }
+
+    #[tokio::test]
+    async fn test_append_to_page_rejects_oversized_content() {
+        let server = MockServer::start(vec![MockResponse::json(200, json!({ "object": "list", "results": [] }))]).await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+
+        let err = jotter
+            .append_to_page(UpdatePageRequest {
+                page_id: "1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d".to_string(),
+                content: "x".repeat(DEFAULT_MAX_CONTENT_SIZE + 1),
+            })
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+        assert!(server.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_create_new_page_with_empty_content() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "list", "results": [{ "object": "database", "id": "db-1" }] })),
+            MockResponse::json(200, json!({ "object": "database", "id": "db-1", "properties": {} })),
+            MockResponse::json(200, json!({ "object": "page", "id": "page-1", "url": "https://www.notion.so/page-1" })),
+        ])
+        .await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+
+        let result = jotter
+            .create_new_page(AddPageRequest { title: "Blank".to_string(), content: " \n\n ".to_string(), cover_url: None })
+            .await
+            .unwrap();
+
+        let result = result_json(&result);
+        assert_eq!(result["ok"], true);
+        assert_eq!(result["page_id"], "page-1");
+        assert_eq!(result["url"], "https://www.notion.so/page-1");
+        let message = result["message"].as_str().unwrap();
+        assert!(message.starts_with("Created page: Blank — https://www.notion.so/page-1"));
+        assert!(message.contains("created without any blocks"));
+        let body: Value = serde_json::from_str(&server.requests()[2].body).unwrap();
+        assert!(body.get("children").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_create_new_page_creates_missing_ref_db() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "list", "results": [], "has_more": false })),
+            MockResponse::json(200, json!({ "object": "list", "results": [{ "object": "page", "id": "ref-page" }] })),
+            MockResponse::json(200, json!({
+                "object": "database",
+                "id": "bc1211ca-e3f1-4939-ae34-5260b16f627c",
+                "created_time": "2024-05-01T12:30:00.000Z",
+                "title": [{ "type": "text", "text": { "content": "Jot It Down MCP server database" }, "plain_text": "Jot It Down MCP server database" }],
+                "parent": { "type": "page_id", "page_id": "ref-page" },
+                "url": "https://www.notion.so/bc1211cae3f14939ae345260b16f627c",
+                "properties": {
+                    "Name": { "id": "title", "name": "Name", "type": "title", "title": {} },
+                    "Tags": { "id": "flsb", "name": "Tags", "type": "multi_select", "multi_select": { "options": [] } }
+                },
+                "archived": false,
+                "is_inline": false
+            })),
+            MockResponse::json(200, json!({ "object": "database", "id": "bc1211ca-e3f1-4939-ae34-5260b16f627c", "properties": {} })),
+            MockResponse::json(200, json!({ "object": "page", "id": "page-1" })),
+        ])
+        .await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+
+        let result = jotter
+            .create_new_page(AddPageRequest { title: "First".to_string(), content: "note".to_string(), cover_url: None })
+            .await
+            .unwrap();
+
+        assert_eq!(result_json(&result)["page_id"], "page-1");
+        let requests = server.requests();
+        let search: Value = serde_json::from_str(&requests[0].body).unwrap();
+        let created: Value = serde_json::from_str(&requests[2].body).unwrap();
+        assert_eq!(requests[2].path, "/databases");
+        assert_eq!(created["title"][0]["text"]["content"], search["query"]);
+        assert_eq!(requests[3].path, "/databases/bc1211ca-e3f1-4939-ae34-5260b16f627c");
+        let body: Value = serde_json::from_str(&requests[4].body).unwrap();
+        assert_eq!(body["parent"]["database_id"], "bc1211ca-e3f1-4939-ae34-5260b16f627c");
+    }
+
+    #[tokio::test]
+    async fn test_create_new_page_reuses_cached_ref_db() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "list", "results": [{ "object": "database", "id": "db-1" }] })),
+            MockResponse::json(200, json!({ "object": "database", "id": "db-1", "properties": {} })),
+            MockResponse::json(200, json!({ "object": "page", "id": "page-1" })),
+            MockResponse::json(200, json!({ "object": "database", "id": "db-1", "properties": {} })),
+            MockResponse::json(200, json!({ "object": "page", "id": "page-2" })),
+        ])
+        .await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+
+        for title in ["First", "Second"] {
+            jotter
+                .create_new_page(AddPageRequest { title: title.to_string(), content: "note".to_string(), cover_url: None })
+                .await
+                .unwrap();
+        }
+
+        let paths: Vec<String> = server.requests().iter().map(|r| format!("{} {}", r.method, r.path)).collect();
+        assert_eq!(paths, vec![
+            "POST /search",
+            "GET /databases/db-1",
+            "POST /pages",
+            "GET /databases/db-1",
+            "POST /pages",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_create_new_page_forgets_ref_db_on_not_found() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "list", "results": [{ "object": "database", "id": "db-1" }] })),
+            MockResponse::json(404, json!({ "object": "error", "status": 404, "code": "object_not_found", "message": "Could not find database" })),
+        ])
+        .await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+
+        let result = jotter
+            .create_new_page(AddPageRequest { title: "Lost".to_string(), content: "note".to_string(), cover_url: None })
+            .await;
+
+        assert!(result.is_err());
+        assert!(jotter.ref_db_cache.lock().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_create_new_page_takes_title_from_frontmatter() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "list", "results": [{ "object": "database", "id": "db-1" }] })),
+            MockResponse::json(200, json!({ "object": "database", "id": "db-1", "properties": {} })),
+            MockResponse::json(200, json!({ "object": "page", "id": "page-1" })),
+        ])
+        .await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+
+        let content = "---\ntitle: Weekly review\ntags: [work]\n---\nShipped the release";
+        let result = jotter
+            .create_new_page(AddPageRequest { title: " ".to_string(), content: content.to_string(), cover_url: None })
+            .await
+            .unwrap();
+
+        assert!(result_json(&result)["message"].as_str().unwrap().starts_with("Created page: Weekly review"));
+        let body: Value = serde_json::from_str(&server.requests()[2].body).unwrap();
+        assert_eq!(body["properties"]["Name"]["title"][0]["text"]["content"], "Weekly review");
+        let children = body["children"].as_array().unwrap();
+        assert_eq!(children.len(), 1);
+        assert_eq!(children[0]["paragraph"]["rich_text"][0]["text"]["content"], "Shipped the release");
+    }
+
+    #[tokio::test]
+    async fn test_bulk_create_pages_reports_each_item() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "list", "results": [{ "object": "database", "id": "db-1" }] })),
+            // Serves both the schema lookups and the page creations
+            MockResponse::json(200, json!({ "object": "page", "id": "page-1", "properties": {} })),
+        ])
+        .await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+        let pages = vec![
+            AddPageRequest { title: "First".to_string(), content: "one".to_string(), cover_url: None },
+            AddPageRequest { title: "Too long".to_string(), content: "x".repeat(DEFAULT_MAX_CONTENT_SIZE + 1), cover_url: None },
+            AddPageRequest { title: "Third".to_string(), content: "three".to_string(), cover_url: None },
+        ];
+
+        let result = jotter.bulk_create_pages(BulkCreatePagesRequest { pages }).await.unwrap();
+
+        let result = result_json(&result);
+        assert_eq!(result["ok"], true);
+        assert_eq!(result["message"], "Created 2 of 3 pages");
+        let results = &result["results"];
+        assert_eq!(results[0]["page_id"], "page-1");
+        let error = results[1]["error"].as_str().unwrap();
+        assert!(
+            error.starts_with(&format!("error occurred: content is {} characters, more than the {} allowed", DEFAULT_MAX_CONTENT_SIZE + 1, DEFAULT_MAX_CONTENT_SIZE)),
+            "{}",
+            error
+        );
+        assert!(results[1].get("page_id").is_none());
+        assert_eq!(results[2]["title"], "Third");
+        assert_eq!(results[2]["page_id"], "page-1");
+        let posts: Vec<Value> = server
+            .requests()
+            .iter()
+            .filter(|r| r.method == "POST" && r.path == "/pages")
+            .map(|r| serde_json::from_str(&r.body).unwrap())
+            .collect();
+        assert_eq!(posts.len(), 2);
+        let titles: Vec<&Value> = posts.iter().map(|body| &body["properties"]["Name"]["title"][0]["text"]["content"]).collect();
+        assert!(titles.contains(&&json!("First")) && titles.contains(&&json!("Third")));
+    }
+
+    #[tokio::test]
+    async fn test_bulk_create_pages_keeps_every_warning() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "list", "results": [{ "object": "database", "id": "db-1" }] })),
+            MockResponse::json(200, json!({ "object": "database", "id": "db-1", "properties": {} })),
+            MockResponse::json(200, json!({ "object": "page", "id": "page-1" })),
+            MockResponse::json(400, json!({ "object": "error", "status": 400, "code": "validation_error", "message": "bad block" })),
+        ])
+        .await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+        let content = (0..150).map(|i| format!("- item {}", i)).collect::<Vec<_>>().join("\n");
+        let pages = vec![AddPageRequest { title: "Long".to_string(), content, cover_url: Some("ftp://example.com/cover.png".to_string()) }];
+
+        let result = jotter.bulk_create_pages(BulkCreatePagesRequest { pages }).await.unwrap();
+
+        let result = result_json(&result);
+        let item = &result["results"][0];
+        assert_eq!(item["page_id"], "page-1");
+        let warnings = item["warnings"].as_array().unwrap();
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].as_str().unwrap().contains("cover_url 'ftp://example.com/cover.png'"));
+        assert!(warnings[1].as_str().unwrap().starts_with("the page is incomplete"), "{}", warnings[1]);
+    }
+
+    #[test]
+    fn test_summary_links_skip_drafts() {
+        let summary = "# Summary\n\n[Introduction](README.md)\n\n- [Start](start.md)\n  - [Deeper](nested/deeper.md)\n- [Draft]()\n- [Site](https://example.com)\n";
+        assert_eq!(summary_links(summary), vec![
+            ("Introduction".to_string(), "README.md".to_string()),
+            ("Start".to_string(), "start.md".to_string()),
+            ("Deeper".to_string(), "nested/deeper.md".to_string()),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_import_mdbook_creates_page_per_chapter() {
+        let dir = temp_book_dir("import");
+        let chapters = vec![
+            MdBookChapter { name: "Start".to_string(), content: "# Start\nFirst steps".to_string() },
+            MdBookChapter { name: "Next".to_string(), content: "# Next\nMore".to_string() },
+        ];
+        write_mdbook(&dir, "Rust \"Notes\"", &chapters).unwrap();
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "list", "results": [{ "object": "database", "id": "db-1" }] })),
+            // Serves both the schema lookups and the page creations
+            MockResponse::json(200, json!({ "object": "page", "id": "page-1", "properties": {} })),
+        ])
+        .await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+
+        let result = jotter.import_mdbook(dir.display().to_string()).await.unwrap();
+
+        let result = result_json(&result);
+        assert_eq!(result["message"], "Imported 3 of 3 chapters of Rust \"Notes\"");
+        assert_eq!(result["results"][1]["title"], "Rust \"Notes\" — Start");
+        let created: Vec<Value> = server
+            .requests()
+            .iter()
+            .filter(|r| r.method == "POST" && r.path == "/pages")
+            .map(|r| serde_json::from_str(&r.body).unwrap())
+            .collect();
+        assert_eq!(created.len(), 3);
+        let start = created
+            .iter()
+            .find(|body| body["properties"]["Name"]["title"][0]["text"]["content"] == "Rust \"Notes\" — Start")
+            .unwrap();
+        assert_eq!(start["children"][1]["paragraph"]["rich_text"][0]["text"]["content"], "First steps");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_import_mdbook_requires_summary() {
+        let dir = temp_book_dir("import-missing");
+        fs::create_dir_all(&dir).unwrap();
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url("http://127.0.0.1:9"));
+
+        let err = jotter.import_mdbook(dir.display().to_string()).await.unwrap_err();
+
+        assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+        assert!(err.message.contains("SUMMARY.md"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_health_check_reports_bot_and_workspace() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({
+                "object": "user",
+                "id": "bot-1",
+                "name": "Jotdown",
+                "type": "bot",
+                "bot": { "owner": { "type": "workspace", "workspace": true }, "workspace_name": "Notes HQ" }
+            })),
+        ])
+        .await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+
+        let result = jotter.health_check().await.unwrap();
+
+        assert_eq!(result_json(&result), json!({
+            "ok": true,
+            "message": "Notion token is valid: bot Jotdown in workspace Notes HQ",
+            "bot": "Jotdown",
+            "workspace": "Notes HQ"
+        }));
+        assert_eq!(server.requests()[0].path, "/users/me");
+    }
+
+    #[tokio::test]
+    async fn test_health_check_unauthorized() {
+        let server = MockServer::start(vec![
+            MockResponse::json(401, json!({
+                "object": "error",
+                "status": 401,
+                "code": "unauthorized",
+                "message": "API token is invalid."
+            })),
+        ])
+        .await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+
+        let err = jotter.health_check().await.unwrap_err();
+
+        assert!(err.message.contains("401 unauthorized"));
+        assert!(err.message.contains("NOTION_TOKEN"));
+    }
+
+    #[tokio::test]
+    async fn test_delete_block_not_found() {
+        let server = MockServer::start(vec![
+            MockResponse::json(404, json!({
+                "object": "error",
+                "status": 404,
+                "code": "object_not_found",
+                "message": "Could not find block with ID: 0b1c2d3e-4f5a-6b7c-8d9e-0f1a2b3c4d5e."
+            })),
+        ])
+        .await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+
+        let err = jotter.delete_block("0b1c2d3e4f5a6b7c8d9e0f1a2b3c4d5e".to_string()).await.unwrap_err();
+
+        assert_eq!(err.code, rmcp::model::ErrorCode::RESOURCE_NOT_FOUND);
+        assert!(err.message.contains("block 0b1c2d3e-4f5a-6b7c-8d9e-0f1a2b3c4d5e not found"));
+        assert_eq!(server.requests()[0].method, "DELETE");
+    }
+
+    #[tokio::test]
+    async fn test_delete_block_accepts_block_link() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "block", "id": "0b1c2d3e-4f5a-6b7c-8d9e-0f1a2b3c4d5e" })),
+        ])
+        .await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+
+        let result = jotter
+            .delete_block(
+                "https://www.notion.so/Groceries-1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d#0b1c2d3e4f5a6b7c8d9e0f1a2b3c4d5e".to_string(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result_json(&result)["block_id"], "0b1c2d3e-4f5a-6b7c-8d9e-0f1a2b3c4d5e");
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/blocks/0b1c2d3e-4f5a-6b7c-8d9e-0f1a2b3c4d5e");
+    }
+
+    #[tokio::test]
+    async fn test_delete_block_rejects_invalid_id() {
+        let server = MockServer::start(vec![]).await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+
+        let err = jotter.delete_block("block-1".to_string()).await.unwrap_err();
+
+        assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+        assert!(server.requests().is_empty());
+    }
+
+    fn titled_page(id: &str, title: &str) -> Value {
+        json!({
+            "object": "page",
+            "id": id,
+            "last_edited_time": "2024-05-01T12:30:00.000Z",
+            "properties": { "Name": { "type": "title", "title": [{ "plain_text": title }] } }
+        })
+    }
+
+    #[test]
+    fn test_max_scan_from_env_value() {
+        assert_eq!(max_scan_from(None), DEFAULT_MAX_SCAN);
+        assert_eq!(max_scan_from(Some(" 20 ".to_string())), 20);
+        assert_eq!(max_scan_from(Some("0".to_string())), DEFAULT_MAX_SCAN);
+        assert_eq!(max_scan_from(Some("100000".to_string())), MAX_QUERY_RESULTS);
+    }
+
+    #[tokio::test]
+    async fn test_search_content_stops_at_scan_cap() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "list", "results": [{ "object": "database", "id": "db-1" }] })),
+            MockResponse::json(200, json!({
+                "object": "list",
+                "results": [titled_page("page-1", "Groceries"), titled_page("page-2", "Chores")],
+                "has_more": true,
+                "next_cursor": "more"
+            })),
+            MockResponse::json(200, json!({ "object": "list", "results": [], "has_more": false })),
+        ])
+        .await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+
+        let search = jotter.search_content_matches("groceries", 2).await.unwrap();
+
+        assert!(search.truncated);
+        assert_eq!(search.matches.len(), 1);
+        let requests = server.requests();
+        let queries: Vec<&RecordedRequest> = requests.iter().filter(|r| r.path == "/databases/db-1/query").collect();
+        assert_eq!(queries.len(), 1);
+        assert_eq!(serde_json::from_str::<Value>(&queries[0].body).unwrap()["page_size"], 2);
+        assert_eq!(requests.iter().filter(|r| r.method == "GET").count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_update_page_is_alias_of_replace_page_content() {
+        let children = || MockResponse::json(200, json!({ "results": [{ "id": "old-1" }], "has_more": false, "next_cursor": null }));
+        let block = || MockResponse::json(200, json!({ "object": "block" }));
+        // list, delete, append for each call
+        let server = MockServer::start(vec![children(), block(), block(), children(), block(), block()]).await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+        let request = || UpdatePageRequest {
+            page_id: "1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d".to_string(),
+            content: "- Dune".to_string(),
+        };
+
+        let updated = result_json(&jotter.update_page(request()).await.unwrap());
+        let replaced = result_json(&jotter.replace_page_content(request()).await.unwrap());
+
+        assert_eq!(updated, replaced);
+        assert_eq!(updated["deleted"], 1);
+        assert_eq!(updated["written"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_append_by_title_rejects_several_matches() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "list", "results": [
+                titled_page("page-1", "Reading list"),
+                titled_page("page-2", "Reading list archive"),
+                titled_page("page-3", "reading list "),
+            ] })),
+        ])
+        .await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+
+        let err = jotter
+            .append_by_title(AppendByTitleRequest { title: "Reading List".to_string(), content: "- Dune".to_string() })
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+        assert!(err.message.contains("2 pages are titled 'Reading List'"), "{}", err.message);
+        assert!(err.message.contains("page-1") && err.message.contains("page-3"));
+        assert!(!err.message.contains("page-2"));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_append_by_title_appends_to_single_match() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "list", "results": [
+                titled_page("1a2b3c4d-5e6f-7a8b-9c0d-1e2f3a4b5c6d", "Reading list"),
+                titled_page("page-2", "Reading list archive"),
+            ] })),
+            MockResponse::json(200, json!({ "object": "list", "results": [] })),
+        ])
+        .await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+
+        let result = jotter
+            .append_by_title(AppendByTitleRequest { title: "reading list".to_string(), content: "- Dune".to_string() })
+            .await
+            .unwrap();
+
+        assert_eq!(result_json(&result)["page_id"], "1a2b3c4d-5e6f-7a8b-9c0d-1e2f3a4b5c6d");
+        let requests = server.requests();
+        assert_eq!(requests[1].method, "PATCH");
+        assert_eq!(requests[1].path, "/blocks/1a2b3c4d-5e6f-7a8b-9c0d-1e2f3a4b5c6d/children");
+    }
+
+    #[tokio::test]
+    async fn test_append_by_title_rejects_oversized_content_before_search() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "list", "results": [titled_page("page-1", "Reading list")] })),
+        ])
+        .await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+
+        let err = jotter
+            .append_by_title(AppendByTitleRequest {
+                title: "Reading list".to_string(),
+                content: "x".repeat(DEFAULT_MAX_CONTENT_SIZE + 1),
+            })
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+        assert!(server.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_quick_note_appends_bullet_to_inbox() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "list", "results": [{ "object": "database", "id": "db-1" }] })),
+            MockResponse::json(200, json!({ "object": "list", "results": [
+                {
+                    "object": "page",
+                    "id": "other-inbox",
+                    "parent": { "type": "page_id", "page_id": "page-9" },
+                    "properties": { "title": { "type": "title", "title": [{ "plain_text": "Inbox" }] } }
+                },
+                {
+                    "object": "page",
+                    "id": "inbox-1",
+                    "parent": { "type": "database_id", "database_id": "db-1" },
+                    "properties": { "Name": { "type": "title", "title": [{ "plain_text": "Inbox" }] } }
+                }
+            ] })),
+            MockResponse::json(200, json!({ "object": "list", "results": [] })),
+        ])
+        .await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+
+        let result = jotter.quick_note("call the plumber".to_string()).await.unwrap();
+
+        assert_eq!(result_json(&result)["page_id"], "inbox-1");
+        let requests = server.requests();
+        assert_eq!(requests[2].method, "PATCH");
+        assert_eq!(requests[2].path, "/blocks/inbox-1/children");
+        let body: Value = serde_json::from_str(&requests[2].body).unwrap();
+        let children = body["children"].as_array().unwrap();
+        assert_eq!(children.len(), 1);
+        assert_eq!(children[0]["type"], "bulleted_list_item");
+        let text = children[0]["bulleted_list_item"]["rich_text"][0]["text"]["content"].as_str().unwrap();
+        assert!(text.ends_with(" — call the plumber"), "{}", text);
+    }
+
+    #[test]
+    fn test_quick_note_blocks_keep_one_line() {
+        let time = DateTime::parse_from_rfc3339("2024-05-01T12:30:00Z").unwrap().with_timezone(&Utc);
+        let blocks = quick_note_blocks(" buy\nmilk ", time, Tz::UTC);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(
+            blocks[0]["bulleted_list_item"]["rich_text"][0]["text"]["content"],
+            "2024-05-01 12:30 UTC — buy milk"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_page_properties_rejects_bad_value() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "page", "properties": {
+                "Date": { "id": "b2", "type": "date", "date": null }
+            } })),
+        ])
+        .await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+        let properties = json!({ "Date": "tomorrow" }).as_object().unwrap().clone();
+
+        let err = jotter
+            .set_page_properties(SetPagePropertiesRequest {
+                page_id: "1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d".to_string(),
+                properties,
+            })
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+        assert!(err.message.contains("'tomorrow' is not a date"));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_delete_page_accepts_page_url() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "page", "archived": true })),
+        ])
+        .await;
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url(&server.url));
+
+        let result = jotter
+            .delete_page("https://www.notion.so/Groceries-1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d?pvs=4".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(result_json(&result)["page_id"], "1a2b3c4d-5e6f-7a8b-9c0d-1e2f3a4b5c6d");
+        assert_eq!(server.requests()[0].path, "/pages/1a2b3c4d-5e6f-7a8b-9c0d-1e2f3a4b5c6d");
+    }
+
+    #[tokio::test]
+    async fn test_read_page_rejects_invalid_id() {
+        let jotter = Jotter::new(Notion::new("secret").unwrap().with_base_url("http://127.0.0.1:9"));
+
+        let err = jotter.read_page("groceries".to_string()).await.unwrap_err();
+
+        assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+        assert!(err.message.contains("not a Notion page id or URL"));
+    }
+
+    #[tokio::test]
+    async fn test_stop_mdbook_without_running_server() {
+        let jotter = Jotter::new(Notion::new("secret").unwrap());
+        let result = jotter.stop_mdbook("/no/such/book".to_string()).await.unwrap();
+        assert_eq!(result_json(&result), json!({ "ok": true, "message": "no server running for /no/such/book" }));
+    }
+
+    /// Executable shell script standing in for the mdbook binary
+    #[cfg(unix)]
+    fn fake_mdbook(dir: &Path, script: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let binary = dir.join("fake-mdbook");
+        fs::write(&binary, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        fs::set_permissions(&binary, fs::Permissions::from_mode(0o755)).unwrap();
+        binary
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_build_mdbook_site_returns_book_dir() {
+        let dir = temp_book_dir("build-ok");
+        write_mdbook(&dir, "Notes", &[]).unwrap();
+        let binary = fake_mdbook(&dir, r#"[ "$1" = build ] && mkdir -p book && touch book/index.html"#);
+
+        let output_dir = build_mdbook_site(binary.to_str().unwrap(), &dir).unwrap();
+
+        assert_eq!(output_dir, dir.join("book"));
+        assert!(output_dir.join("index.html").is_file());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_build_mdbook_site_reports_stderr_errors() {
+        let dir = temp_book_dir("build-error");
+        write_mdbook(&dir, "Notes", &[]).unwrap();
+        let binary = fake_mdbook(
+            &dir,
+            "echo ' INFO Book building has started' >&2\necho ' ERROR Chapter file not found, ./missing.md' >&2\nexit 101",
+        );
+
+        let err = build_mdbook_site(binary.to_str().unwrap(), &dir).unwrap_err().to_string();
+
+        assert!(err.contains("Chapter file not found, ./missing.md"), "{}", err);
+        assert!(!err.contains("Book building has started"), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_build_mdbook_site_requires_book_toml() {
+        let dir = temp_book_dir("build-not-a-book");
+        fs::create_dir_all(&dir).unwrap();
+        let err = build_mdbook_site("mdbook", &dir).unwrap_err().to_string();
+        assert!(err.contains("book.toml is missing"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_directory_url_encodes_spaces_and_non_ascii() {
+        assert_eq!(
+            directory_url(Path::new("/home/me/My Books/Rust notes")).as_deref(),
+            Some("file:///home/me/My%20Books/Rust%20notes/")
+        );
+        assert_eq!(
+            directory_url(Path::new("/home/me/Bücher/#1")).as_deref(),
+            Some("file:///home/me/B%C3%BCcher/%231/")
+        );
+        assert_eq!(directory_url(Path::new("relative/book")), None);
+    }
+
+    #[test]
+    fn test_ensure_mdbook_installed_reports_missing_binary() {
+        let err = ensure_mdbook_installed("jotdown-test-missing-mdbook").unwrap_err();
+        assert!(err.message.contains("cargo install mdbook"));
+        assert!(err.message.contains("jotdown-test-missing-mdbook"));
+    }
+
+    #[test]
+    fn test_rank_pages_finds_body_text() {
+        let pages = vec![
+            ("page-1".to_string(), "Groceries".to_string(), "milk, eggs".to_string()),
+            ("page-2".to_string(), "Meeting notes".to_string(), "Discussed the Quarterly Roadmap".to_string()),
+        ];
+        let matches = rank_pages("quarterly roadmap", &pages);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].page_id, "page-2");
+        assert_eq!(matches[0].title, "Meeting notes");
+    }
+
+    #[test]
+    fn test_rank_pages_prefers_title_then_body_count() {
+        let pages = vec![
+            ("page-1".to_string(), "Misc".to_string(), "rust".to_string()),
+            ("page-2".to_string(), "Other".to_string(), "rust and more rust".to_string()),
+            ("page-3".to_string(), "Rust tips".to_string(), String::new()),
+        ];
+        let ids: Vec<String> = rank_pages("Rust", &pages).into_iter().map(|m| m.page_id).collect();
+        assert_eq!(ids, vec!["page-3", "page-2", "page-1"]);
+        assert!(rank_pages("  ", &pages).is_empty());
+    }
 }
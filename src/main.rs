@@ -3,13 +3,20 @@ pub mod jot;
 pub mod notion;
 pub mod util;
 
-use anyhow::Result;
+#[cfg(test)]
+mod mock_server;
+
+use anyhow::{Context, Result};
 use jot::Jotter;
 use notion::Notion;
-use rmcp::{ServiceExt, transport::stdio};
+use rmcp::{ServiceExt, transport::{SseServer, stdio}};
 use std::env;
+use std::net::SocketAddr;
 use tracing_subscriber::{self, EnvFilter};
 
+// Address the SSE transport listens on unless JOTDOWN_BIND is set
+const DEFAULT_SSE_BIND: &str = "127.0.0.1:8000";
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize the tracing subscriber with file and stdout logging
@@ -22,10 +29,38 @@ async fn main() -> Result<()> {
     tracing::info!("Starting Jotdown MCP server");
 
     dotenv::dotenv().ok();
-    let token = env::var("NOTION_TOKEN").expect("NOTION_TOKEN not found");
-    let data_store = Notion::new(&token);
+    let data_store = match env::var("NOTION_TOKEN")
+        .map_err(anyhow::Error::from)
+        .and_then(|token| Notion::new(&token))
+    {
+        Ok(data_store) => data_store,
+        Err(e) => {
+            tracing::error!(
+                "NOTION_TOKEN is missing or invalid ({}). Set NOTION_TOKEN in your environment or .env",
+                e
+            );
+            std::process::exit(1);
+        }
+    };
+
+    let jotter = Jotter::new(data_store);
+    let transport = env::var("JOTDOWN_TRANSPORT").unwrap_or_else(|_| "stdio".to_string());
+    match transport.trim().to_lowercase().as_str() {
+        "stdio" => serve_stdio(jotter).await,
+        "sse" => {
+            let bind = env::var("JOTDOWN_BIND").unwrap_or_else(|_| DEFAULT_SSE_BIND.to_string());
+            serve_sse(jotter, &bind).await
+        }
+        other => {
+            tracing::error!("unknown JOTDOWN_TRANSPORT '{}', expected 'stdio' or 'sse'", other);
+            std::process::exit(1);
+        }
+    }
+}
 
-    let service = Jotter::new(data_store)
+/// Serve a single MCP client over stdin/stdout
+async fn serve_stdio(jotter: Jotter) -> Result<()> {
+    let service = jotter
         .serve(stdio())
         .await
         .inspect_err(|e| {
@@ -35,3 +70,25 @@ async fn main() -> Result<()> {
     service.waiting().await?;
     Ok(())
 }
+
+/// Serve any number of MCP clients over HTTP with server-sent events until ctrl-c
+///
+/// Clients connect to `http://<bind>/sse` and post messages to `/message`.
+async fn serve_sse(jotter: Jotter, bind: &str) -> Result<()> {
+    let addr: SocketAddr = bind
+        .parse()
+        .with_context(|| format!("invalid JOTDOWN_BIND address '{}'", bind))?;
+    let server = SseServer::serve(addr)
+        .await
+        .inspect_err(|e| {
+            tracing::error!("serving error: {:?}", e);
+        })?;
+    tracing::info!("Listening for SSE clients on http://{}/sse", addr);
+
+    // Clients share one Jotter so running mdbook servers are tracked in one place
+    let ct = server.with_service(move || jotter.clone());
+
+    tokio::signal::ctrl_c().await?;
+    ct.cancel();
+    Ok(())
+}
@@ -1,39 +1,166 @@
 use crate::util::{
-    CREATE_DATABASE_URL, CREATE_PAGE_URL, ReqMethod, SEARCH_BY_FILTER_URL, send_request,
+    BLOCKS_PATH, DATABASES_PATH, NOTION_API_URL, NotionError, PAGES_PATH, ReqMethod,
+    SEARCH_BY_FILTER_PATH, USERS_PATH, send_request,
 };
-use anyhow::Result;
+use crate::formatter::{SUMMARY_MAX_CHARS, summarize_blocks};
+use anyhow::{Result, anyhow};
+use regex::Regex;
 use reqwest::StatusCode;
+use rmcp::schemars;
 use serde_json::Value;
 use serde_json::json;
-use std::time::Duration;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
 use tokio::time::sleep;
 
 #[derive(Debug, Clone, serde::Deserialize)]
 pub struct Notion {
     token: String,
+    #[serde(default = "default_base_url")]
+    base_url: String,
+    #[serde(default = "page_icon")]
+    page_icon: String,
+    #[serde(default = "db_icon")]
+    db_icon: String,
+}
+
+fn default_base_url() -> String {
+    NOTION_API_URL.to_string()
+}
+
+/// Title of the reference database, used both to create and to search for it
+pub const REF_DB_TITLE: &str = "Jot It Down MCP server database";
+
+/// Icon of created pages unless `JOTDOWN_PAGE_ICON` is set
+pub const DEFAULT_PAGE_ICON: &str = "🥬";
+
+/// Icon of the reference database unless `JOTDOWN_DB_ICON` is set
+pub const DEFAULT_DB_ICON: &str = "📝";
+
+/// Most code points accepted in an icon, enough for emoji with modifiers or joiners
+const MAX_ICON_CHARS: usize = 8;
+
+fn page_icon() -> String {
+    icon_from(std::env::var("JOTDOWN_PAGE_ICON").ok(), DEFAULT_PAGE_ICON)
+}
+
+fn db_icon() -> String {
+    icon_from(std::env::var("JOTDOWN_DB_ICON").ok(), DEFAULT_DB_ICON)
+}
+
+/// Use `value` as an icon when it is an emoji, `default` otherwise
+fn icon_from(value: Option<String>, default: &str) -> String {
+    let Some(value) = value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty()) else {
+        return default.to_string();
+    };
+    // Emoji, including multi code point ones (skin tones, ZWJ sequences), have no
+    // ASCII, letters, digits or spaces in them
+    let emoji = value.chars().count() <= MAX_ICON_CHARS
+        && !value.chars().any(|c| c.is_ascii() || c.is_alphanumeric() || c.is_whitespace());
+    if emoji {
+        value
+    } else {
+        tracing::warn!("ignoring icon '{}', expected a single emoji", value);
+        default.to_string()
+    }
 }
 
 impl Notion {
-    pub fn new(token: &str) -> Self {
-        Self {
-            token: token.to_owned(),
+    /// Creates a Notion client for an integration token
+    ///
+    /// The token is trimmed and prefixed with `Bearer ` when the raw secret
+    /// was given, so it can be used directly as the `Authorization` header.
+    pub fn new(token: &str) -> Result<Self> {
+        let token = token.trim();
+        let secret = match token.get(..6) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("bearer")
+                && (token.len() == 6 || token[6..].starts_with(char::is_whitespace)) =>
+            {
+                token[6..].trim_start()
+            }
+            _ => token,
+        };
+        if secret.is_empty() {
+            return Err(anyhow!("NOTION_TOKEN is empty"));
         }
+        Ok(Self {
+            token: format!("Bearer {}", secret),
+            base_url: default_base_url(),
+            page_icon: page_icon(),
+            db_icon: db_icon(),
+        })
+    }
+
+    /// Points the client at another API root, used to talk to a mock server in tests
+    #[cfg(test)]
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
     }
 
-    pub async fn search_ref(&self, title: &str, ref_type: &str) -> Result<(StatusCode, Value)> {
+    /// Searches pages or databases by title
+    ///
+    /// # Arguments
+    ///
+    /// * `title` - Text to match against titles
+    /// * `ref_type` - Kind of object to search for
+    /// * `direction` - Order of the results by last edit time
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(StatusCode, Value)>` - API status and response
+    pub async fn search_ref(&self, title: &str, ref_type: RefType, direction: SortDirection) -> Result<(StatusCode, Value)> {
         let body = json!({
         "query":title,
             "filter": {
-                "value": ref_type,
+                "value": ref_type.as_filter(),
                 "property": "object"
             },
             "sort":{
-              "direction":"ascending",
+              "direction":direction.as_str(),
               "timestamp":"last_edited_time"
             }
         });
         send_request(
-            SEARCH_BY_FILTER_URL,
+            &self.url(SEARCH_BY_FILTER_PATH),
+            ReqMethod::Post,
+            Some(body),
+            self.token.as_str(),
+        )
+        .await
+    }
+
+    /// Searches pages by title, most recently edited first
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - Text to match against page titles
+    /// * `page_size` - Maximum number of pages to return (at most 100)
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(StatusCode, Value)>` - API status and response
+    pub async fn search_pages(&self, query: &str, page_size: u32) -> Result<(StatusCode, Value)> {
+        let body = json!({
+            "query": query,
+            "filter": {
+                "value": RefType::Page.as_filter(),
+                "property": "object"
+            },
+            "sort": {
+                "direction": "descending",
+                "timestamp": "last_edited_time"
+            },
+            "page_size": page_size.clamp(1, 100)
+        });
+        send_request(
+            &self.url(SEARCH_BY_FILTER_PATH),
             ReqMethod::Post,
             Some(body),
             self.token.as_str(),
@@ -49,13 +176,13 @@ impl Notion {
             },
             "icon": {
                 "type": "emoji",
-                "emoji": "📝"
+                "emoji": self.db_icon
               },
             "title": [
                 {
                     "type": "text",
                     "text": {
-                        "content": "Jot It Down McpServer Database"
+                        "content": REF_DB_TITLE
                     }
                 }
             ],
@@ -65,11 +192,20 @@ impl Notion {
                 },
                 "Content": {
                     "rich_text": {}
+                },
+                "Tags": {
+                    "multi_select": {}
+                },
+                "Status": {
+                    "select": {}
+                },
+                "Date": {
+                    "date": {}
                 }
             }
         });
         send_request(
-            CREATE_DATABASE_URL,
+            &self.url(DATABASES_PATH),
             ReqMethod::Post,
             Some(body),
             self.token.as_str(),
@@ -79,84 +215,254 @@ impl Notion {
 
 
 
+    /// Queries the pages stored in a database, most recently edited first
+    ///
+    /// Follows `next_cursor` until `limit` pages are collected or the database
+    /// has no more, never fetching more than [`MAX_QUERY_RESULTS`] pages.
+    ///
+    /// # Arguments
+    ///
+    /// * `database_id` - ID of the Notion database
+    /// * `limit` - Maximum number of pages to return
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(StatusCode, Value)>` - API status and the last response, with the
+    ///   `results` of every request concatenated and `has_more` set when pages
+    ///   beyond `limit` were left out
+    pub async fn query_database(&self, database_id: &str, limit: u32) -> Result<(StatusCode, Value)> {
+        let url = self.url(&format!("{}/{}/query", DATABASES_PATH, database_id));
+        let limit = limit.clamp(1, MAX_QUERY_RESULTS) as usize;
+        let mut results: Vec<Value> = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let mut body = json!({
+                "page_size": (limit - results.len()).min(100),
+                "sorts": [
+                    {
+                        "timestamp": "last_edited_time",
+                        "direction": "descending"
+                    }
+                ]
+            });
+            if let Some(start_cursor) = &cursor {
+                body["start_cursor"] = json!(start_cursor);
+            }
+
+            let (status, mut response) = send_request(
+                &url,
+                ReqMethod::Post,
+                Some(body),
+                self.token.as_str(),
+            ).await?;
+
+            if let Some(page) = response.get_mut("results").and_then(|v| v.as_array_mut()) {
+                results.append(page);
+            }
+            cursor = response
+                .get("next_cursor")
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_string());
+            let has_more = response.get("has_more").and_then(|v| v.as_bool()).unwrap_or(false);
+            if !has_more || cursor.is_none() || results.len() >= limit {
+                // `has_more` tells the caller whether pages were left out
+                response["has_more"] = json!(results.len() > limit || (has_more && cursor.is_some()));
+                results.truncate(limit);
+                response["results"] = Value::Array(results);
+                return Ok((status, response));
+            }
+        }
+    }
+
     /// Creates a page using formatted blocks
     ///
+    /// The database schema is read so the title goes into whichever property
+    /// is the title column. When the database has a `Content` text property
+    /// it is filled with a plain-text summary of the blocks so the table view
+    /// shows a preview of each note. Tags are stored in a `Tags` multi-select
+    /// property and left out for databases without one.
+    ///
     /// # Arguments
     ///
     /// * `database_id` - ID of the Notion database
     /// * `title` - Title of the page
     /// * `blocks` - Formatted content blocks
+    /// * `cover_url` - External image shown as the page cover
+    /// * `tags` - Tags of the page
     ///
     /// # Returns
     ///
-    /// * `Result<(StatusCode, Value)>` - API status and response
+    /// * `Result<PageCreation>` - API response for the page and any batch that failed
     pub async fn create_page_with_blocks(
         &self,
         database_id: &str,
         title: &str,
         blocks: &[Value],
-    ) -> Result<(StatusCode, Value)> {
+        cover_url: Option<&str>,
+        tags: &[String],
+    ) -> Result<PageCreation> {
+        let (_, database) = self.fetch_database(database_id).await?;
+        let schema = &database["properties"];
+
+        let parent = json!({
+            "database_id": database_id
+        });
+        let mut properties = json!({
+            title_property_name(schema): {
+                "title": [
+                    {
+                        "text": {
+                            "content": title
+                        }
+                    }
+                ]
+            }
+        });
+        if schema[CONTENT_PROPERTY]["type"] == "rich_text" {
+            properties[CONTENT_PROPERTY] = json!({
+                "rich_text": [
+                    {
+                        "text": {
+                            "content": summarize_blocks(blocks, SUMMARY_MAX_CHARS)
+                        }
+                    }
+                ]
+            });
+        }
+        if !tags.is_empty() {
+            if schema[TAGS_PROPERTY]["type"] == "multi_select" {
+                properties[TAGS_PROPERTY] = json!({ "multi_select": multi_select_options(tags) });
+            } else {
+                tracing::warn!("database {} has no {} multi-select property, tags were not stored", database_id, TAGS_PROPERTY);
+            }
+        }
+        self.create_page(parent, properties, blocks, cover_url).await
+    }
+
+    /// Creates a page nested directly under another page
+    ///
+    /// # Arguments
+    ///
+    /// * `parent_page_id` - ID of the page the new page is created in
+    /// * `title` - Title of the page
+    /// * `blocks` - Formatted content blocks
+    ///
+    /// # Returns
+    ///
+    /// * `Result<PageCreation>` - API response for the page and any batch that failed
+    pub async fn create_child_page_with_blocks(
+        &self,
+        parent_page_id: &str,
+        title: &str,
+        blocks: &[Value],
+    ) -> Result<PageCreation> {
+        let parent = json!({
+            "type": "page_id",
+            "page_id": parent_page_id
+        });
+        let properties = json!({
+            "title": {
+                "title": [
+                    {
+                        "text": {
+                            "content": title
+                        }
+                    }
+                ]
+            }
+        });
+        self.create_page(parent, properties, blocks, None).await
+    }
+
+    /// Creates a page with the first 100 blocks, then appends the rest in batches
+    ///
+    /// Once the page exists a failed batch no longer fails the call, it is
+    /// reported in [`PageCreation::incomplete`] so the missing blocks can be
+    /// appended later.
+    async fn create_page(
+        &self,
+        parent: Value,
+        properties: Value,
+        blocks: &[Value],
+        cover_url: Option<&str>,
+    ) -> Result<PageCreation> {
         // Take the first 100 blocks (Notion API limit)
-        let first_batch = if blocks.len() > 100 { &blocks[..100] } else { blocks };
+        let first_batch = &blocks[..blocks.len().min(MAX_BLOCKS_PER_REQUEST)];
         
-        let body = json!({
-            "parent": {
-                "database_id": database_id
-            },
+        let mut body = json!({
+            "parent": parent,
             "icon": {
-                "emoji": "🥬"
-            },
-            "properties": {
-                "Name": {
-                    "title": [
-                        {
-                            "text": {
-                                "content": title
-                            }
-                        }
-                    ]
-                },
-                "Content": {
-                    "rich_text": [
-                        {
-                            "text": {
-                                "content": "Content noted by Jotdown MCP server"
-                            }
-                        }
-                    ]
-                }
+                "emoji": self.page_icon
             },
-            "children": first_batch
+            "properties": properties
         });
+        if let Some(url) = cover_url {
+            body["cover"] = json!({
+                "type": "external",
+                "external": { "url": url }
+            });
+        }
+        // Notion may reject an empty children array, leave it out for empty pages
+        if !first_batch.is_empty() {
+            body["children"] = json!(first_batch);
+        }
         
         // Create the page with the first batch of blocks
         let (status, response) = send_request(
-            CREATE_PAGE_URL,
+            &self.url(PAGES_PATH),
             ReqMethod::Post,
             Some(body),
             self.token.as_str(),
         ).await?;
         
         // If there are more blocks and the page was created successfully
-        if blocks.len() > 100 && status.is_success() {
-            if let Some(page_id) = response.get("id").and_then(|v| v.as_str()) {
-                // Add remaining blocks in batches of 100
-                for chunk_start in (100..blocks.len()).step_by(100) {
-                    let chunk_end = (chunk_start + 100).min(blocks.len());
-                    let chunk = &blocks[chunk_start..chunk_end];
-                    
-                    let _ = self.append_blocks(page_id, chunk).await?;
-                    
-                    // Add a small delay to avoid rate limits
-                    sleep(Duration::from_millis(100)).await;
-                }
-            }
+        let mut incomplete = None;
+        if blocks.len() > MAX_BLOCKS_PER_REQUEST
+            && status.is_success()
+            && let Some(page_id) = response.get("id").and_then(|v| v.as_str())
+            && let Err(mut e) = self.post_blocks_in_batches(page_id, &blocks[MAX_BLOCKS_PER_REQUEST..]).await
+        {
+            // Count the request that created the page as the first batch
+            e.batch += 1;
+            e.batches += 1;
+            e.blocks_written += MAX_BLOCKS_PER_REQUEST;
+            tracing::warn!("page {} created without all of its content: {}", page_id, e);
+            incomplete = Some(e);
         }
         
-        Ok((status, response))
+        Ok(PageCreation { status, response, incomplete })
     }
     
-    /// Adds blocks to an existing page
+    /// Copies the content of a page into a new page of a database
+    ///
+    /// Blocks are fetched with all nested children and stripped of read-only
+    /// fields before being posted again. Sub pages, sub databases and blocks
+    /// the API can't create are left out.
+    ///
+    /// # Arguments
+    ///
+    /// * `source_page_id` - ID of the page to copy
+    /// * `database_id` - ID of the database the copy is created in
+    /// * `title` - Title of the new page
+    ///
+    /// # Returns
+    ///
+    /// * `Result<PageCreation>` - API response for the new page and any batch that failed
+    pub async fn duplicate_page(
+        &self,
+        source_page_id: &str,
+        database_id: &str,
+        title: &str,
+    ) -> Result<PageCreation> {
+        let blocks = self.fetch_all_blocks(source_page_id).await?;
+        self.create_page_with_blocks(database_id, title, &copyable_blocks(&blocks), None, &[]).await
+    }
+
+    /// Adds blocks to the end of an existing page
+    ///
+    /// Any number of blocks can be given, they are sent in batches of at most
+    /// 100 like the create and update paths.
     ///
     /// # Arguments
     ///
@@ -165,13 +471,22 @@ impl Notion {
     ///
     /// # Returns
     ///
-    /// * `Result<(StatusCode, Value)>` - API status and response
+    /// * `Result<(StatusCode, Value)>` - API status and response of the last batch
     pub async fn append_blocks(
         &self,
         page_id: &str,
         blocks: &[Value],
     ) -> Result<(StatusCode, Value)> {
-        let url = format!("https://api.notion.com/v1/blocks/{}/children", page_id);
+        Ok(self.post_blocks_in_batches(page_id, blocks).await?)
+    }
+
+    /// Sends a single append request, the caller keeps it within 100 blocks
+    async fn append_batch(
+        &self,
+        page_id: &str,
+        blocks: &[Value],
+    ) -> Result<(StatusCode, Value)> {
+        let url = self.url(&format!("{}/{}/children", BLOCKS_PATH, page_id));
         
         let body = json!({
             "children": blocks
@@ -185,70 +500,1440 @@ impl Notion {
         ).await
     }
 
-    pub async fn fetch_page_content(&self, page_id: &str) -> Result<(StatusCode, Value)> {
-        let page_content_url = format!(
-            "https://api.notion.com/v1/blocks/{}/children?page_size=100",
-            page_id
-        );
+    /// Retrieves a database object with its property schema
+    ///
+    /// # Arguments
+    ///
+    /// * `database_id` - ID of the Notion database
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(StatusCode, Value)>` - API status and response
+    pub async fn fetch_database(&self, database_id: &str) -> Result<(StatusCode, Value)> {
+        let url = self.url(&format!("{}/{}", DATABASES_PATH, database_id));
         send_request(
-            page_content_url.as_str(),
+            &url,
             ReqMethod::Get,
             None,
             self.token.as_str(),
-        )
-        .await
+        ).await
     }
 
+    /// Retrieves the bot user of the integration the token belongs to
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(StatusCode, Value)>` - API status and response
+    pub async fn fetch_bot_user(&self) -> Result<(StatusCode, Value)> {
+        let url = self.url(&format!("{}/me", USERS_PATH));
+        send_request(
+            &url,
+            ReqMethod::Get,
+            None,
+            self.token.as_str(),
+        ).await
+    }
 
-    
-    /// Updates a page with new formatted blocks
+    /// Retrieves a page object with its properties
     ///
     /// # Arguments
     ///
     /// * `page_id` - ID of the Notion page
-    /// * `blocks` - Formatted content blocks
     ///
     /// # Returns
     ///
     /// * `Result<(StatusCode, Value)>` - API status and response
-    pub async fn update_page_with_blocks(
-        &self,
-        page_id: &str,
-        blocks: &[Value],
-    ) -> Result<(StatusCode, Value)> {
-        // The Notion API doesn't allow replacing all blocks at once
-        // So we need to add the new blocks
-        
-        // Take the first 100 blocks (Notion API limit)
-        let first_batch = if blocks.len() > 100 { &blocks[..100] } else { blocks };
-        
-        let url = format!("https://api.notion.com/v1/blocks/{}/children", page_id);
-        
+    pub async fn fetch_page(&self, page_id: &str) -> Result<(StatusCode, Value)> {
+        let url = self.url(&format!("{}/{}", PAGES_PATH, page_id));
+        send_request(
+            &url,
+            ReqMethod::Get,
+            None,
+            self.token.as_str(),
+        ).await
+    }
+
+    /// Sets the title of a page
+    ///
+    /// The page is read first to find the name of its title property, so
+    /// databases whose title column isn't called `Name` work too.
+    ///
+    /// # Arguments
+    ///
+    /// * `page_id` - ID of the Notion page
+    /// * `title` - New title of the page
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(StatusCode, Value)>` - API status and response
+    pub async fn update_page_title(&self, page_id: &str, title: &str) -> Result<(StatusCode, Value)> {
+        let (_, page) = self.fetch_page(page_id).await?;
+        let title_property = title_property_name(&page["properties"]);
+
+        let url = self.url(&format!("{}/{}", PAGES_PATH, page_id));
         let body = json!({
-            "children": first_batch
+            "properties": {
+                title_property: {
+                    "title": [
+                        {
+                            "text": {
+                                "content": title
+                            }
+                        }
+                    ]
+                }
+            }
         });
-        
-        let (status, response) = send_request(
+
+        send_request(
             &url,
             ReqMethod::Patch,
             Some(body),
             self.token.as_str(),
-        ).await?;
-        
-        // If there are more blocks and the update was successful
-        if blocks.len() > 100 && status.is_success() {
-            // Add remaining blocks in batches of 100
-            for chunk_start in (100..blocks.len()).step_by(100) {
-                let chunk_end = (chunk_start + 100).min(blocks.len());
-                let chunk = &blocks[chunk_start..chunk_end];
-                
-                let _ = self.append_blocks(page_id, chunk).await?;
-                
-                // Add a small delay to avoid rate limits
-                sleep(Duration::from_millis(100)).await;
-            }
+        ).await
+    }
+
+    /// Sets properties of a page from plain values
+    ///
+    /// The page is read first so each value is converted to the JSON of the
+    /// property's type, e.g. `"Done"` for a select or `"2024-05-01"` for a date.
+    ///
+    /// # Arguments
+    ///
+    /// * `page_id` - ID of the Notion page
+    /// * `values` - Property names and the values to set them to
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(StatusCode, Value)>` - API status and response
+    pub async fn set_page_properties(
+        &self,
+        page_id: &str,
+        values: &serde_json::Map<String, Value>,
+    ) -> Result<(StatusCode, Value)> {
+        let (_, page) = self.fetch_page(page_id).await?;
+        let existing = &page["properties"];
+        let mut properties = serde_json::Map::new();
+        for (name, value) in values {
+            let Some(property_type) = existing[name.as_str()]["type"].as_str() else {
+                let known: Vec<&String> = existing.as_object().map(|p| p.keys().collect()).unwrap_or_default();
+                return Err(anyhow!("the page has no property '{}', it has {:?}", name, known));
+            };
+            let property = property_value(property_type, value)
+                .map_err(|e| anyhow!("property '{}': {}", name, e))?;
+            properties.insert(name.clone(), property);
         }
-        
-        Ok((status, response))
+
+        let url = self.url(&format!("{}/{}", PAGES_PATH, page_id));
+        send_request(
+            &url,
+            ReqMethod::Patch,
+            Some(json!({ "properties": properties })),
+            self.token.as_str(),
+        ).await
+    }
+
+    /// Moves a page under another page
+    ///
+    /// The parent is patched first. The Notion API rejects or ignores parent
+    /// changes for most pages, in which case the page is copied under the new
+    /// parent and the original archived, so the moved page gets a new id and
+    /// link and loses its comments, history and database properties.
+    ///
+    /// # Arguments
+    ///
+    /// * `page_id` - ID of the page to move
+    /// * `parent_page_id` - ID of the new parent page
+    ///
+    /// # Returns
+    ///
+    /// * `Result<MoveOutcome>` - Whether the page itself moved or was copied
+    pub async fn move_page(&self, page_id: &str, parent_page_id: &str) -> Result<MoveOutcome> {
+        let url = self.url(&format!("{}/{}", PAGES_PATH, page_id));
+        let body = json!({
+            "parent": {
+                "type": "page_id",
+                "page_id": parent_page_id
+            }
+        });
+
+        match send_request(&url, ReqMethod::Patch, Some(body), self.token.as_str()).await {
+            Ok((_, response)) => {
+                let new_parent = response["parent"]["page_id"].as_str().unwrap_or("");
+                if same_id(new_parent, parent_page_id) {
+                    return Ok(MoveOutcome::Moved);
+                }
+            }
+            Err(e) if e.downcast_ref::<NotionError>().is_some_and(|e| e.status == StatusCode::BAD_REQUEST) => {}
+            Err(e) => return Err(e),
+        }
+
+        tracing::info!("notion kept the parent of page {}, copying it instead", page_id);
+        let (_, page) = self.fetch_page(page_id).await?;
+        let blocks = self.fetch_all_blocks(page_id).await?;
+        let copy = self
+            .create_child_page_with_blocks(parent_page_id, &page_title(&page), &copyable_blocks(&blocks))
+            .await?;
+        // Keep the original when the copy is missing content
+        if let Some(e) = copy.incomplete {
+            return Err(anyhow!(
+                "copy {} under the new parent is missing content, the original was kept: {}",
+                copy.response["id"].as_str().unwrap_or("(unknown id)"),
+                e
+            ));
+        }
+        self.archive_page(page_id).await?;
+        Ok(MoveOutcome::Copied(copy.response))
+    }
+
+    /// Archives (soft deletes) a page, moving it to the trash. The Notion API
+    /// can't delete a page permanently; use [`Notion::unarchive_page`] to restore it
+    ///
+    /// # Arguments
+    ///
+    /// * `page_id` - ID of the Notion page
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(StatusCode, Value)>` - API status and response
+    pub async fn archive_page(&self, page_id: &str) -> Result<(StatusCode, Value)> {
+        self.set_archived(page_id, true).await
+    }
+
+    /// Restores an archived page from the trash
+    ///
+    /// # Arguments
+    ///
+    /// * `page_id` - ID of the Notion page
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(StatusCode, Value)>` - API status and response
+    pub async fn unarchive_page(&self, page_id: &str) -> Result<(StatusCode, Value)> {
+        self.set_archived(page_id, false).await
+    }
+
+    async fn set_archived(&self, page_id: &str, archived: bool) -> Result<(StatusCode, Value)> {
+        let url = self.url(&format!("{}/{}", PAGES_PATH, page_id));
+
+        let body = json!({
+            "archived": archived
+        });
+
+        send_request(
+            &url,
+            ReqMethod::Patch,
+            Some(body),
+            self.token.as_str(),
+        ).await
+    }
+
+    /// Fetches the direct child blocks of a page or block, following pagination cursors
+    ///
+    /// # Arguments
+    ///
+    /// * `block_id` - ID of the Notion page or block
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<Value>>` - Direct child blocks in order
+    pub async fn fetch_child_blocks(&self, block_id: &str) -> Result<Vec<Value>> {
+        let mut blocks = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let mut url = self.url(&format!(
+                "{}/{}/children?page_size=100",
+                BLOCKS_PATH, block_id
+            ));
+            if let Some(start_cursor) = &cursor {
+                url.push_str(&format!("&start_cursor={}", start_cursor));
+            }
+
+            let (_, response) = send_request(
+                url.as_str(),
+                ReqMethod::Get,
+                None,
+                self.token.as_str(),
+            ).await?;
+
+            if let Some(results) = response.get("results").and_then(|v| v.as_array()) {
+                blocks.extend(results.iter().cloned());
+            }
+
+            cursor = response
+                .get("next_cursor")
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_string());
+            let has_more = response.get("has_more").and_then(|v| v.as_bool()).unwrap_or(false);
+            if !has_more || cursor.is_none() {
+                break;
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    /// Fetches every child block of a page, following pagination cursors
+    ///
+    /// Nested blocks (such as sub-list items) are fetched recursively and
+    /// stored under the parent's `children` array.
+    ///
+    /// # Arguments
+    ///
+    /// * `page_id` - ID of the Notion page
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<Value>>` - All child blocks in order
+    pub async fn fetch_all_blocks(&self, page_id: &str) -> Result<Vec<Value>> {
+        let mut blocks = self.fetch_child_blocks(page_id).await?;
+
+        for block in blocks.iter_mut() {
+            let has_children = block.get("has_children").and_then(|v| v.as_bool()).unwrap_or(false);
+            let block_type = block.get("type").and_then(|v| v.as_str()).unwrap_or("").to_string();
+            // Sub pages and databases are separate documents, not nested content
+            if !has_children || block_type == "child_page" || block_type == "child_database" {
+                continue;
+            }
+            if let Some(block_id) = block.get("id").and_then(|v| v.as_str()).map(|v| v.to_string()) {
+                let children = Box::pin(self.fetch_all_blocks(&block_id)).await?;
+                block[block_type.as_str()]["children"] = Value::Array(children);
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    /// Deletes (archives) a single block
+    ///
+    /// Notion moves the block to the trash, the same as deleting it in the app.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_id` - ID of the Notion block
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(StatusCode, Value)>` - API status and response
+    pub async fn delete_block(&self, block_id: &str) -> Result<(StatusCode, Value)> {
+        let url = self.url(&format!("{}/{}", BLOCKS_PATH, block_id));
+
+        send_request(
+            &url,
+            ReqMethod::Delete,
+            None,
+            self.token.as_str(),
+        ).await
+    }
+
+    /// Deletes every top-level block of a page
+    ///
+    /// All block ids are fetched first, since deleting while paginating would
+    /// shift the cursor, then deleted one after another like any other work on
+    /// a single page. No further block is deleted once one fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `page_id` - ID of the Notion page
+    ///
+    /// # Returns
+    ///
+    /// * `Result<usize>` - Number of blocks deleted
+    pub async fn delete_children(&self, page_id: &str) -> Result<usize> {
+        let children = self.fetch_child_blocks(page_id).await?;
+        let block_ids: Vec<&str> = children.iter().filter_map(|b| b.get("id").and_then(|v| v.as_str())).collect();
+        for (deleted, block_id) in block_ids.iter().enumerate() {
+            if let Err(error) = self.delete_block(block_id).await {
+                let message = format!("{} (deleted {} of {} blocks)", error, deleted, block_ids.len());
+                return Err(error.context(message));
+            }
+        }
+        Ok(block_ids.len())
+    }
+
+    /// Replaces the body of a page: deletes its blocks, then writes the new ones
+    ///
+    /// # Arguments
+    ///
+    /// * `page_id` - ID of the Notion page
+    /// * `blocks` - Formatted content blocks
+    ///
+    /// # Returns
+    ///
+    /// * `Result<PageReplacement>` - Number of blocks deleted and written
+    pub async fn replace_page_content(&self, page_id: &str, blocks: &[Value]) -> Result<PageReplacement> {
+        let deleted = self.delete_children(page_id).await?;
+        if blocks.is_empty() {
+            return Ok(PageReplacement { deleted, written: 0 });
+        }
+        self.post_blocks_in_batches(page_id, blocks).await?;
+        Ok(PageReplacement { deleted, written: blocks.len() })
+    }
+
+    pub async fn fetch_page_content(&self, page_id: &str) -> Result<(StatusCode, Value)> {
+        let page_content_url = self.url(&format!(
+            "{}/{}/children?page_size=100",
+            BLOCKS_PATH, page_id
+        ));
+        send_request(
+            page_content_url.as_str(),
+            ReqMethod::Get,
+            None,
+            self.token.as_str(),
+        )
+        .await
+    }
+
+
+    
+    /// Appends blocks to a page in batches of at most 100, the Notion API limit
+    ///
+    /// Batches are sent back to back while the recent request rate stays under
+    /// Notion's limit and only wait once it would be exceeded. Rate limited
+    /// responses are retried after their `Retry-After` by `send_request`.
+    ///
+    /// # Arguments
+    ///
+    /// * `page_id` - ID of the Notion page
+    /// * `blocks` - Content blocks to add; an empty slice still sends one request
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(StatusCode, Value), BatchError>` - API status and response of the last
+    ///   batch, or the first batch that failed; later batches are not sent
+    async fn post_blocks_in_batches(
+        &self,
+        page_id: &str,
+        blocks: &[Value],
+    ) -> std::result::Result<(StatusCode, Value), BatchError> {
+        let mut rate_window = RateWindow::new(NOTION_REQUESTS_PER_SECOND, RATE_WINDOW);
+        let batches: Vec<&[Value]> = if blocks.is_empty() {
+            vec![blocks]
+        } else {
+            blocks.chunks(MAX_BLOCKS_PER_REQUEST).collect()
+        };
+        let mut last = None;
+
+        for (index, batch) in batches.iter().enumerate() {
+            let delay = rate_window.delay(Instant::now());
+            if !delay.is_zero() {
+                sleep(delay).await;
+            }
+            match self.append_batch(page_id, batch).await {
+                Ok(response) => last = Some(response),
+                Err(source) => {
+                    return Err(BatchError {
+                        batch: index + 1,
+                        batches: batches.len(),
+                        blocks_written: index * MAX_BLOCKS_PER_REQUEST,
+                        source,
+                    });
+                }
+            }
+            rate_window.record(Instant::now());
+        }
+
+        Ok(last.expect("at least one batch is always sent"))
+    }
+}
+
+/// Most pages a single [`Notion::query_database`] call collects across cursors
+pub const MAX_QUERY_RESULTS: u32 = 1000;
+
+/// Page created by [`Notion::create_page_with_blocks`] and similar methods
+#[derive(Debug)]
+pub struct PageCreation {
+    pub status: StatusCode,
+    /// API response for the new page
+    pub response: Value,
+    /// Batch that failed after the page was created, leaving it without its later blocks
+    pub incomplete: Option<BatchError>,
+}
+
+/// Result of [`Notion::replace_page_content`]
+#[derive(Debug, PartialEq, Eq)]
+pub struct PageReplacement {
+    /// Blocks removed from the page
+    pub deleted: usize,
+    /// Top-level blocks written in their place
+    pub written: usize,
+}
+
+/// A batch of blocks the API rejected after the earlier batches were written
+#[derive(Debug)]
+pub struct BatchError {
+    /// Number of the failed batch, starting at 1
+    pub batch: usize,
+    /// Number of batches the blocks were split into
+    pub batches: usize,
+    /// Blocks written by the batches before the failed one
+    pub blocks_written: usize,
+    pub source: anyhow::Error,
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "batch {} of {} failed after {} blocks were written: {}",
+            self.batch, self.batches, self.blocks_written, self.source
+        )
+    }
+}
+
+impl std::error::Error for BatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// Kind of object a search is restricted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefType {
+    Page,
+    Database,
+}
+
+impl RefType {
+    /// Value of the search `object` filter for this kind
+    pub fn as_filter(&self) -> &'static str {
+        match self {
+            RefType::Page => "page",
+            RefType::Database => "database",
+        }
+    }
+}
+
+/// Order of search results by last edit time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    /// Oldest edit first
+    Ascending,
+    /// Most recent edit first
+    #[default]
+    Descending,
+}
+
+impl SortDirection {
+    /// Value of the search `sort.direction` field
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortDirection::Ascending => "ascending",
+            SortDirection::Descending => "descending",
+        }
+    }
+}
+
+/// Result of [`Notion::move_page`]
+#[derive(Debug)]
+pub enum MoveOutcome {
+    /// The page itself now lives under the new parent
+    Moved,
+    /// The page was copied under the new parent and the original archived;
+    /// holds the API response for the copy
+    Copied(Value),
+}
+
+/// Plain text title of a page, read from whichever property has the title type
+pub fn page_title(page: &Value) -> String {
+    page.get("properties")
+        .and_then(|v| v.as_object())
+        .and_then(|props| props.values().find(|p| p.get("type").and_then(|t| t.as_str()) == Some("title")))
+        .and_then(|p| p.get("title"))
+        .and_then(|v| v.as_array())
+        .map(|parts| {
+            parts
+                .iter()
+                .filter_map(|part| part.get("plain_text").and_then(|v| v.as_str()))
+                .collect::<String>()
+        })
+        .unwrap_or_default()
+}
+
+/// Title property used when a schema doesn't name one, as in the reference database
+const DEFAULT_TITLE_PROPERTY: &str = "Name";
+
+/// Text property of the reference database holding a summary of each note
+const CONTENT_PROPERTY: &str = "Content";
+
+/// Multi-select property of the reference database holding the tags of each note
+const TAGS_PROPERTY: &str = "Tags";
+
+/// Property value JSON for a plain value, by the type of the property
+///
+/// `null` clears the property. Strings are accepted for numbers, checkboxes
+/// and comma separated multi-select options.
+fn property_value(property_type: &str, value: &Value) -> std::result::Result<Value, String> {
+    if value.is_null() {
+        let cleared = match property_type {
+            "title" | "rich_text" | "multi_select" => json!([]),
+            _ => Value::Null,
+        };
+        return Ok(json!({ property_type: cleared }));
+    }
+    let text = match value {
+        Value::String(text) => Some(text.trim().to_string()),
+        Value::Number(_) | Value::Bool(_) => Some(value.to_string()),
+        _ => None,
+    };
+    let expect_text = || text.clone().ok_or_else(|| format!("expected text for a {} property, got {}", property_type, value));
+    let property = match property_type {
+        "title" | "rich_text" => json!([{ "type": "text", "text": { "content": expect_text()? } }]),
+        "select" | "status" => json!({ "name": expect_text()?.replace(',', "") }),
+        "multi_select" => {
+            let tags: Vec<String> = match value {
+                Value::Array(items) => items.iter().map(|item| item.as_str().map_or_else(|| item.to_string(), str::to_string)).collect(),
+                _ => expect_text()?.split(',').map(str::to_string).collect(),
+            };
+            Value::Array(multi_select_options(&tags))
+        }
+        "date" => match value {
+            Value::Object(range) => {
+                let start = range.get("start").and_then(|v| v.as_str()).ok_or("a date range needs a start")?;
+                let mut date = json!({ "start": date_value(start)? });
+                if let Some(end) = range.get("end").and_then(|v| v.as_str()) {
+                    date["end"] = json!(date_value(end)?);
+                }
+                date
+            }
+            _ => json!({ "start": date_value(&expect_text()?)? }),
+        },
+        "number" => {
+            let number = expect_text()?.parse::<f64>().map_err(|_| format!("'{}' is not a number", value))?;
+            json!(number)
+        }
+        "checkbox" => match expect_text()?.to_lowercase().as_str() {
+            "true" | "yes" => json!(true),
+            "false" | "no" => json!(false),
+            other => return Err(format!("'{}' is not true or false", other)),
+        },
+        "url" | "email" | "phone_number" => json!(expect_text()?),
+        other => return Err(format!("{} properties can't be set", other)),
+    };
+    Ok(json!({ property_type: property }))
+}
+
+/// Check a date (`2024-05-01`) or date and time (RFC 3339) value for Notion
+fn date_value(text: &str) -> std::result::Result<String, String> {
+    let text = text.trim();
+    let valid = chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d").is_ok()
+        || chrono::DateTime::parse_from_rfc3339(text).is_ok();
+    if valid {
+        Ok(text.to_string())
+    } else {
+        Err(format!("'{}' is not a date like 2024-05-01 or 2024-05-01T09:30:00Z", text))
+    }
+}
+
+/// Multi-select options for tags, without duplicates
+///
+/// Notion rejects commas in option names so they are dropped.
+fn multi_select_options(tags: &[String]) -> Vec<Value> {
+    let mut seen = std::collections::HashSet::new();
+    tags.iter()
+        .map(|tag| tag.replace(',', "").trim().to_string())
+        .filter(|tag| !tag.is_empty() && seen.insert(tag.clone()))
+        .map(|tag| json!({ "name": tag }))
+        .collect()
+}
+
+/// Name of the title property in page properties or a database schema
+fn title_property_name(properties: &Value) -> String {
+    properties
+        .as_object()
+        .and_then(|props| {
+            props
+                .iter()
+                .find(|(_, prop)| prop.get("type").and_then(|t| t.as_str()) == Some("title"))
+                .map(|(name, _)| name.clone())
+        })
+        .unwrap_or_else(|| DEFAULT_TITLE_PROPERTY.to_string())
+}
+
+/// A Notion id, dashed or not, at the end of an id or URL path, compiled once
+static NOTION_ID_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(?:^|[^0-9a-f])([0-9a-f]{8})-?([0-9a-f]{4})-?([0-9a-f]{4})-?([0-9a-f]{4})-?([0-9a-f]{12})$").unwrap()
+});
+
+/// Normalize a Notion id or page URL into the dashed 8-4-4-4-12 id form
+///
+/// # Arguments
+///
+/// * `input` - A page id with or without dashes, or a Notion page URL
+///
+/// # Returns
+///
+/// * `Result<String>` - Lowercase dashed id
+pub fn normalize_id(input: &str) -> Result<String> {
+    let trimmed = input.trim();
+    let path = trimmed.split(['?', '#']).next().unwrap_or_default().trim_end_matches('/');
+    let captures = NOTION_ID_REGEX
+        .captures(path)
+        .ok_or_else(|| anyhow!("'{}' is not a Notion page id or URL", trimmed))?;
+    let parts: Vec<&str> = (1..=5).map(|i| &captures[i]).collect();
+    Ok(parts.join("-").to_lowercase())
+}
+
+/// Compare Notion ids, which may be written with or without dashes
+pub fn same_id(a: &str, b: &str) -> bool {
+    let normalize = |id: &str| id.replace('-', "").to_lowercase();
+    !a.is_empty() && normalize(a) == normalize(b)
+}
+
+/// Most blocks the Notion API accepts in a single request
+const MAX_BLOCKS_PER_REQUEST: usize = 100;
+
+/// Average number of requests per second Notion allows an integration
+const NOTION_REQUESTS_PER_SECOND: usize = 3;
+
+/// Period the request rate is measured over
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Recent requests, used to wait only when the next one would exceed the rate limit
+struct RateWindow {
+    sent: VecDeque<Instant>,
+    limit: usize,
+    window: Duration,
+}
+
+impl RateWindow {
+    fn new(limit: usize, window: Duration) -> Self {
+        Self { sent: VecDeque::new(), limit, window }
+    }
+
+    /// Time to wait at `now` before another request fits in the window
+    fn delay(&mut self, now: Instant) -> Duration {
+        while self.sent.front().is_some_and(|sent| now.duration_since(*sent) >= self.window) {
+            self.sent.pop_front();
+        }
+        match self.sent.front() {
+            Some(oldest) if self.sent.len() >= self.limit => self.window - now.duration_since(*oldest),
+            _ => Duration::ZERO,
+        }
+    }
+
+    fn record(&mut self, now: Instant) {
+        self.sent.push_back(now);
+    }
+}
+
+/// Block types that can be read but not created through the API
+const UNCOPYABLE_BLOCK_TYPES: [&str; 4] = ["child_page", "child_database", "link_preview", "unsupported"];
+
+/// Rebuild fetched blocks with only the fields accepted when creating blocks
+///
+/// Ids, timestamps, authors and other read-only fields are dropped; nested
+/// children are cleaned the same way.
+fn copyable_blocks(blocks: &[Value]) -> Vec<Value> {
+    blocks
+        .iter()
+        .filter_map(|block| {
+            let block_type = block.get("type").and_then(|v| v.as_str())?;
+            if UNCOPYABLE_BLOCK_TYPES.contains(&block_type) {
+                return None;
+            }
+            let mut content = block.get(block_type).cloned().unwrap_or_else(|| json!({}));
+            if let Some(children) = content.get("children").and_then(|v| v.as_array()) {
+                content["children"] = Value::Array(copyable_blocks(children));
+            }
+            Some(json!({
+                "object": "block",
+                "type": block_type,
+                block_type: content
+            }))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{MockResponse, MockServer};
+
+    #[test]
+    fn test_new_adds_bearer_prefix() {
+        let notion = Notion::new("secret_abc").unwrap();
+        assert_eq!(notion.token, "Bearer secret_abc");
+    }
+
+    #[test]
+    fn test_new_keeps_existing_bearer_prefix() {
+        let notion = Notion::new("  Bearer secret_abc\n").unwrap();
+        assert_eq!(notion.token, "Bearer secret_abc");
+        let notion = Notion::new("bearer secret_abc").unwrap();
+        assert_eq!(notion.token, "Bearer secret_abc");
+    }
+
+    #[test]
+    fn test_new_rejects_empty_token() {
+        assert!(Notion::new("").is_err());
+        assert!(Notion::new("Bearer   ").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_delete_children_deletes_each_block() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({
+                "results": [{ "id": "block-1" }, { "id": "block-2" }],
+                "has_more": false,
+                "next_cursor": null
+            })),
+            MockResponse::json(200, json!({ "object": "block", "archived": true })),
+        ])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+
+        let deleted = notion.delete_children("page-1").await.unwrap();
+        assert_eq!(deleted, 2);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].path, "/blocks/page-1/children?page_size=100");
+        let deletes: Vec<(&str, &str)> = requests[1..].iter().map(|r| (r.method.as_str(), r.path.as_str())).collect();
+        assert_eq!(deletes, vec![("DELETE", "/blocks/block-1"), ("DELETE", "/blocks/block-2")]);
+    }
+
+    #[tokio::test]
+    async fn test_delete_children_of_empty_page() {
+        let server = MockServer::start(vec![MockResponse::json(200, json!({
+            "results": [],
+            "has_more": false,
+            "next_cursor": null
+        }))])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+
+        assert_eq!(notion.delete_children("page-1").await.unwrap(), 0);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_replace_page_content_deletes_then_writes() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({
+                "results": [{ "id": "old-1" }, { "id": "old-2" }, { "id": "old-3" }],
+                "has_more": false,
+                "next_cursor": null
+            })),
+            MockResponse::json(200, json!({ "object": "block" })),
+        ])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+        let blocks = vec![json!({ "type": "divider", "divider": {} }); 2];
+
+        let replacement = notion.replace_page_content("page-1", &blocks).await.unwrap();
+        assert_eq!(replacement, PageReplacement { deleted: 3, written: 2 });
+
+        let requests = server.requests();
+        let methods: Vec<&str> = requests.iter().map(|r| r.method.as_str()).collect();
+        assert_eq!(methods, vec!["GET", "DELETE", "DELETE", "DELETE", "PATCH"]);
+        assert_eq!(requests[4].path, "/blocks/page-1/children");
+        let body: Value = serde_json::from_str(&requests[4].body).unwrap();
+        assert_eq!(body["children"].as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_delete_children_stops_at_failed_delete() {
+        let results: Vec<Value> = (0..150).map(|i| json!({ "id": format!("block-{}", i) })).collect();
+        let mut responses = vec![MockResponse::json(200, json!({ "results": results, "has_more": false, "next_cursor": null }))];
+        responses.extend((0..120).map(|_| MockResponse::json(200, json!({ "object": "block", "archived": true }))));
+        responses.push(MockResponse::json(404, json!({ "object": "error", "status": 404, "code": "object_not_found", "message": "gone" })));
+        let server = MockServer::start(responses).await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+
+        let err = notion.delete_children("page-1").await.unwrap_err();
+
+        assert!(err.to_string().ends_with("gone (deleted 120 of 150 blocks)"), "{}", err);
+        assert!(err.downcast_ref::<NotionError>().is_some());
+        // Blocks after the failed one were never deleted
+        let requests = server.requests();
+        assert_eq!(requests.len(), 122);
+        assert_eq!(requests[121].path, "/blocks/block-120");
+    }
+
+    #[tokio::test]
+    async fn test_update_page_title_uses_title_property_name() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({
+                "object": "page",
+                "id": "page-1",
+                "properties": {
+                    "Content": { "id": "abc", "type": "rich_text", "rich_text": [] },
+                    "Title": { "id": "title", "type": "title", "title": [] }
+                }
+            })),
+            MockResponse::json(200, json!({ "object": "page", "id": "page-1" })),
+        ])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+
+        notion.update_page_title("page-1", "Renamed").await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].path, "/pages/page-1");
+        assert_eq!(requests[1].method, "PATCH");
+        assert_eq!(requests[1].path, "/pages/page-1");
+        let body: Value = serde_json::from_str(&requests[1].body).unwrap();
+        assert_eq!(body, json!({
+            "properties": {
+                "Title": { "title": [{ "text": { "content": "Renamed" } }] }
+            }
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_page_omits_read_only_fields() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({
+                "results": [
+                    {
+                        "object": "block",
+                        "id": "block-1",
+                        "created_time": "2024-01-01T00:00:00.000Z",
+                        "last_edited_time": "2024-01-01T00:00:00.000Z",
+                        "created_by": { "object": "user", "id": "user-1" },
+                        "has_children": false,
+                        "archived": false,
+                        "type": "paragraph",
+                        "paragraph": { "rich_text": [{ "type": "text", "text": { "content": "Template" } }] }
+                    },
+                    {
+                        "object": "block",
+                        "id": "block-2",
+                        "has_children": false,
+                        "type": "child_page",
+                        "child_page": { "title": "Sub page" }
+                    }
+                ],
+                "has_more": false,
+                "next_cursor": null
+            })),
+            MockResponse::json(200, json!({ "object": "database", "id": "db-1", "properties": {} })),
+            MockResponse::json(200, json!({ "object": "page", "id": "page-2" })),
+        ])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+
+        notion.duplicate_page("page-1", "db-1", "Copy").await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[2].method, "POST");
+        assert_eq!(requests[2].path, "/pages");
+        let body: Value = serde_json::from_str(&requests[2].body).unwrap();
+        assert_eq!(body["children"], json!([
+            {
+                "object": "block",
+                "type": "paragraph",
+                "paragraph": { "rich_text": [{ "type": "text", "text": { "content": "Template" } }] }
+            }
+        ]));
+    }
+
+    #[test]
+    fn test_rate_window_only_waits_when_full() {
+        let start = Instant::now();
+        let mut window = RateWindow::new(3, Duration::from_secs(1));
+        for offset in [0, 100, 200] {
+            let now = start + Duration::from_millis(offset);
+            assert_eq!(window.delay(now), Duration::ZERO);
+            window.record(now);
+        }
+
+        let now = start + Duration::from_millis(300);
+        assert_eq!(window.delay(now), Duration::from_millis(700));
+        assert_eq!(window.delay(start + Duration::from_millis(1000)), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_post_blocks_in_batches_splits_by_request_limit() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "list", "results": [] })),
+        ])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+        let blocks: Vec<Value> = (0..250)
+            .map(|i| json!({ "type": "paragraph", "paragraph": { "rich_text": [{ "text": { "content": i.to_string() } }] } }))
+            .collect();
+
+        notion.post_blocks_in_batches("page-1", &blocks).await.unwrap();
+
+        let requests = server.requests();
+        let batch_sizes: Vec<usize> = requests
+            .iter()
+            .map(|request| {
+                assert_eq!(request.method, "PATCH");
+                assert_eq!(request.path, "/blocks/page-1/children");
+                let body: Value = serde_json::from_str(&request.body).unwrap();
+                body["children"].as_array().unwrap().len()
+            })
+            .collect();
+        assert_eq!(batch_sizes, vec![100, 100, 50]);
+    }
+
+    #[tokio::test]
+    async fn test_post_blocks_in_batches_single_request_for_small_pages() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "list", "results": [] })),
+        ])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+
+        notion.post_blocks_in_batches("page-1", &[]).await.unwrap();
+
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn test_icon_from_validates_value() {
+        assert_eq!(icon_from(None, DEFAULT_PAGE_ICON), DEFAULT_PAGE_ICON);
+        assert_eq!(icon_from(Some(" 🚀 ".to_string()), DEFAULT_PAGE_ICON), "🚀");
+        assert_eq!(icon_from(Some("👩‍💻".to_string()), DEFAULT_PAGE_ICON), "👩‍💻");
+        assert_eq!(icon_from(Some("J".to_string()), DEFAULT_PAGE_ICON), DEFAULT_PAGE_ICON);
+        assert_eq!(icon_from(Some("é".to_string()), DEFAULT_DB_ICON), DEFAULT_DB_ICON);
+        assert_eq!(icon_from(Some("rocket".to_string()), DEFAULT_PAGE_ICON), DEFAULT_PAGE_ICON);
+        assert_eq!(icon_from(Some("🚀 🚀".to_string()), DEFAULT_DB_ICON), DEFAULT_DB_ICON);
+    }
+
+    #[tokio::test]
+    async fn test_custom_icons_in_create_bodies() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "page", "id": "page-1" })),
+        ])
+        .await;
+        let mut notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+        notion.page_icon = "🚀".to_string();
+        notion.db_icon = "📚".to_string();
+
+        notion.create_page_with_blocks("db-1", "Title", &[], None, &[]).await.unwrap();
+        notion.create_database("page-1").await.unwrap();
+
+        let requests = server.requests();
+        let page: Value = serde_json::from_str(&requests[1].body).unwrap();
+        let database: Value = serde_json::from_str(&requests[2].body).unwrap();
+        assert_eq!(page["icon"]["emoji"], "🚀");
+        assert_eq!(database["icon"]["emoji"], "📚");
+    }
+
+    #[tokio::test]
+    async fn test_create_database_adds_status_and_date() {
+        let server = MockServer::start(vec![MockResponse::json(200, json!({ "object": "database", "id": "db-1" }))]).await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+
+        notion.create_database("page-1").await.unwrap();
+
+        let database: Value = serde_json::from_str(&server.requests()[0].body).unwrap();
+        assert_eq!(database["properties"]["Status"], json!({ "select": {} }));
+        assert_eq!(database["properties"]["Date"], json!({ "date": {} }));
+    }
+
+    #[tokio::test]
+    async fn test_set_page_properties_select_and_date() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({
+                "object": "page",
+                "id": "page-1",
+                "properties": {
+                    "Name": { "id": "title", "type": "title", "title": [] },
+                    "Status": { "id": "a1", "type": "select", "select": null },
+                    "Date": { "id": "b2", "type": "date", "date": null }
+                }
+            })),
+            MockResponse::json(200, json!({ "object": "page", "id": "page-1" })),
+        ])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+        let values = json!({ "Status": "Done", "Date": "2024-05-01" });
+
+        notion.set_page_properties("page-1", values.as_object().unwrap()).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[1].method, "PATCH");
+        assert_eq!(requests[1].path, "/pages/page-1");
+        let body: Value = serde_json::from_str(&requests[1].body).unwrap();
+        assert_eq!(body, json!({
+            "properties": {
+                "Status": { "select": { "name": "Done" } },
+                "Date": { "date": { "start": "2024-05-01" } }
+            }
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_set_page_properties_unknown_property() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "page", "id": "page-1", "properties": {
+                "Name": { "id": "title", "type": "title", "title": [] }
+            } })),
+        ])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+        let values = json!({ "Priority": "High" });
+
+        let err = notion.set_page_properties("page-1", values.as_object().unwrap()).await.unwrap_err();
+
+        assert!(err.to_string().contains("no property 'Priority'"));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn test_property_value_by_type() {
+        assert_eq!(property_value("number", &json!("4.5")).unwrap(), json!({ "number": 4.5 }));
+        assert_eq!(property_value("checkbox", &json!(true)).unwrap(), json!({ "checkbox": true }));
+        assert_eq!(
+            property_value("multi_select", &json!("work, urgent")).unwrap(),
+            json!({ "multi_select": [{ "name": "work" }, { "name": "urgent" }] })
+        );
+        assert_eq!(
+            property_value("date", &json!({ "start": "2024-05-01", "end": "2024-05-03" })).unwrap(),
+            json!({ "date": { "start": "2024-05-01", "end": "2024-05-03" } })
+        );
+        assert_eq!(property_value("select", &Value::Null).unwrap(), json!({ "select": null }));
+        assert!(property_value("date", &json!("next friday")).is_err());
+        assert!(property_value("formula", &json!("x")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_move_page_copies_when_parent_change_rejected() {
+        let server = MockServer::start(vec![
+            MockResponse::json(400, json!({
+                "object": "error",
+                "code": "validation_error",
+                "message": "body.parent should be not present"
+            })),
+            MockResponse::json(200, json!({
+                "object": "page",
+                "id": "page-1",
+                "properties": { "Name": { "type": "title", "title": [{ "plain_text": "Groceries" }] } }
+            })),
+            MockResponse::json(200, json!({
+                "results": [
+                    { "id": "block-1", "type": "paragraph", "has_children": false, "paragraph": { "rich_text": [] } }
+                ],
+                "has_more": false
+            })),
+            MockResponse::json(200, json!({ "object": "page", "id": "page-2" })),
+            MockResponse::json(200, json!({ "object": "page", "id": "page-1", "archived": true })),
+        ])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+
+        let outcome = notion.move_page("page-1", "parent-1").await.unwrap();
+
+        assert!(matches!(outcome, MoveOutcome::Copied(copy) if copy["id"] == "page-2"));
+        let requests = server.requests();
+        let copy: Value = serde_json::from_str(&requests[3].body).unwrap();
+        assert_eq!(copy["parent"], json!({ "type": "page_id", "page_id": "parent-1" }));
+        assert_eq!(copy["properties"]["title"]["title"][0]["text"]["content"], "Groceries");
+        assert_eq!(requests[4].method, "PATCH");
+        assert_eq!(requests[4].path, "/pages/page-1");
+        assert_eq!(requests[4].body, json!({ "archived": true }).to_string());
+    }
+
+    #[test]
+    fn test_normalize_id_from_url() {
+        let id = "1a2b3c4d-5e6f-7a8b-9c0d-1e2f3a4b5c6d";
+        for input in [
+            "https://www.notion.so/workspace/Cafe-Notes-1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d?pvs=4",
+            "https://www.notion.so/1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d#fedcba9876543210fedcba9876543210",
+            "notion.so/Deadbeef-Page-1A2B3C4D5E6F7A8B9C0D1E2F3A4B5C6D/",
+        ] {
+            assert_eq!(normalize_id(input).unwrap(), id, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_normalize_id_dashes() {
+        let id = "1a2b3c4d-5e6f-7a8b-9c0d-1e2f3a4b5c6d";
+        assert_eq!(normalize_id("1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d").unwrap(), id);
+        assert_eq!(normalize_id(&format!(" {} ", id)).unwrap(), id);
+        assert!(normalize_id("not-an-id").is_err());
+        assert!(normalize_id("1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6").is_err());
+        assert!(normalize_id("01a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unarchive_page_body() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "page", "id": "page-1", "archived": false })),
+        ])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+
+        notion.unarchive_page("page-1").await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "PATCH");
+        assert_eq!(requests[0].path, "/pages/page-1");
+        assert_eq!(requests[0].body, json!({ "archived": false }).to_string());
+    }
+
+    #[tokio::test]
+    async fn test_move_page_when_parent_accepted() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({
+                "object": "page",
+                "id": "page-1",
+                "parent": { "type": "page_id", "page_id": "aaaa-bbbb" }
+            })),
+        ])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+
+        let outcome = notion.move_page("page-1", "aaaabbbb").await.unwrap();
+
+        assert!(matches!(outcome, MoveOutcome::Moved));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_query_database_follows_cursor() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({
+                "object": "list",
+                "results": [{ "id": "page-1" }, { "id": "page-2" }],
+                "has_more": true,
+                "next_cursor": "cursor-2"
+            })),
+            MockResponse::json(200, json!({
+                "object": "list",
+                "results": [{ "id": "page-3" }],
+                "has_more": false,
+                "next_cursor": null
+            })),
+        ])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+
+        let (_, response) = notion.query_database("db-1", 101).await.unwrap();
+
+        let ids: Vec<&str> = response["results"].as_array().unwrap().iter().map(|p| p["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["page-1", "page-2", "page-3"]);
+        assert_eq!(response["has_more"], false);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        let first: Value = serde_json::from_str(&requests[0].body).unwrap();
+        assert_eq!(first["page_size"], 100);
+        assert!(first.get("start_cursor").is_none());
+        let second: Value = serde_json::from_str(&requests[1].body).unwrap();
+        assert_eq!(second["start_cursor"], "cursor-2");
+        assert_eq!(second["page_size"], 99);
+    }
+
+    #[tokio::test]
+    async fn test_query_database_stops_at_limit() {
+        let server = MockServer::start(vec![MockResponse::json(200, json!({
+            "object": "list",
+            "results": [{ "id": "page-1" }, { "id": "page-2" }],
+            "has_more": true,
+            "next_cursor": "again"
+        }))])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+
+        let (_, response) = notion.query_database("db-1", 3).await.unwrap();
+
+        assert_eq!(response["results"].as_array().unwrap().len(), 3);
+        assert_eq!(response["has_more"], true);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_search_ref_filters_by_ref_type() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "list", "results": [] })),
+        ])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+
+        for ref_type in [RefType::Page, RefType::Database] {
+            notion.search_ref("Jot It Down", ref_type, SortDirection::Ascending).await.unwrap();
+        }
+
+        let filters: Vec<Value> = server
+            .requests()
+            .iter()
+            .map(|request| serde_json::from_str::<Value>(&request.body).unwrap()["filter"]["value"].clone())
+            .collect();
+        assert_eq!(filters, vec![json!("page"), json!("database")]);
+    }
+
+    #[tokio::test]
+    async fn test_search_ref_sorts_most_recent_first_by_default() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "list", "results": [] })),
+        ])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+
+        notion.search_ref("notes", RefType::Page, SortDirection::default()).await.unwrap();
+
+        let body: Value = serde_json::from_str(&server.requests()[0].body).unwrap();
+        assert_eq!(body["sort"], json!({ "direction": "descending", "timestamp": "last_edited_time" }));
+    }
+
+    #[tokio::test]
+    async fn test_append_blocks_batches_past_request_limit() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "list", "results": [] })),
+        ])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+        let blocks = vec![json!({ "type": "divider", "divider": {} }); 150];
+
+        notion.append_blocks("page-1", &blocks).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        let sizes: Vec<usize> = requests
+            .iter()
+            .map(|r| serde_json::from_str::<Value>(&r.body).unwrap()["children"].as_array().unwrap().len())
+            .collect();
+        assert_eq!(sizes, vec![100, 50]);
+    }
+
+    #[tokio::test]
+    async fn test_create_page_reports_failed_batch() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "database", "id": "db-1", "properties": {} })),
+            MockResponse::json(200, json!({ "object": "page", "id": "page-1" })),
+            MockResponse::json(200, json!({ "object": "list", "results": [] })),
+            MockResponse::json(500, json!({
+                "object": "error",
+                "code": "internal_server_error",
+                "message": "Unexpected error"
+            })),
+        ])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+        let blocks = vec![json!({ "type": "divider", "divider": {} }); 250];
+
+        let creation = notion.create_page_with_blocks("db-1", "Long", &blocks, None, &[]).await.unwrap();
+
+        assert_eq!(creation.response["id"], "page-1");
+        let failure = creation.incomplete.expect("third batch should have failed");
+        assert_eq!((failure.batch, failure.batches, failure.blocks_written), (3, 3, 200));
+        assert!(failure.to_string().contains("Unexpected error"));
+        assert_eq!(server.requests().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_create_page_uses_database_title_property() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({
+                "object": "database",
+                "id": "db-1",
+                "properties": {
+                    "Tags": { "id": "a", "type": "multi_select", "multi_select": {} },
+                    "Title": { "id": "title", "type": "title", "title": {} }
+                }
+            })),
+            MockResponse::json(200, json!({ "object": "page", "id": "page-1" })),
+        ])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+        let blocks = vec![json!({ "type": "paragraph", "paragraph": { "rich_text": [{ "text": { "content": "Hi" } }] } })];
+
+        notion.create_page_with_blocks("db-1", "Greeting", &blocks, None, &[]).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].path, "/databases/db-1");
+        let body: Value = serde_json::from_str(&requests[1].body).unwrap();
+        assert_eq!(body["properties"], json!({
+            "Title": { "title": [{ "text": { "content": "Greeting" } }] }
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_create_page_fills_content_summary_when_present() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({
+                "object": "database",
+                "id": "db-1",
+                "properties": {
+                    "Name": { "id": "title", "type": "title", "title": {} },
+                    "Content": { "id": "b", "type": "rich_text", "rich_text": {} }
+                }
+            })),
+            MockResponse::json(200, json!({ "object": "page", "id": "page-1" })),
+        ])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+        let blocks = vec![json!({ "type": "paragraph", "paragraph": { "rich_text": [{ "text": { "content": "Hi" } }] } })];
+
+        notion.create_page_with_blocks("db-1", "Greeting", &blocks, None, &[]).await.unwrap();
+
+        let body: Value = serde_json::from_str(&server.requests()[1].body).unwrap();
+        assert_eq!(body["properties"]["Name"]["title"][0]["text"]["content"], "Greeting");
+        assert_eq!(body["properties"]["Content"]["rich_text"][0]["text"]["content"], "Hi");
+    }
+
+    #[tokio::test]
+    async fn test_create_child_page_has_page_parent() {
+        let server = MockServer::start(vec![MockResponse::json(200, json!({ "object": "page", "id": "child-1" }))]).await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+        let blocks = vec![json!({ "type": "divider", "divider": {} })];
+
+        let creation = notion.create_child_page_with_blocks("parent-1", "Sub notes", &blocks).await.unwrap();
+        assert_eq!(creation.response["id"], "child-1");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/pages");
+        let body: Value = serde_json::from_str(&requests[0].body).unwrap();
+        assert_eq!(body["parent"], json!({ "type": "page_id", "page_id": "parent-1" }));
+        assert_eq!(body["properties"]["title"]["title"][0]["text"]["content"], "Sub notes");
+        assert_eq!(body["children"], json!(blocks));
+    }
+
+    #[tokio::test]
+    async fn test_create_page_with_tags() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({
+                "object": "database",
+                "id": "db-1",
+                "properties": { "Tags": { "id": "t1", "type": "multi_select", "multi_select": { "options": [] } } }
+            })),
+            MockResponse::json(200, json!({ "object": "page", "id": "page-1" })),
+            MockResponse::json(200, json!({ "object": "database", "id": "db-2", "properties": {} })),
+        ])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+        let tags = vec!["rust".to_string(), "a, b".to_string(), "rust".to_string()];
+
+        notion.create_page_with_blocks("db-1", "Notes", &[], None, &tags).await.unwrap();
+        // Databases without a Tags property still get the page
+        notion.create_page_with_blocks("db-2", "Notes", &[], None, &tags).await.unwrap();
+
+        let requests = server.requests();
+        let body: Value = serde_json::from_str(&requests[1].body).unwrap();
+        assert_eq!(body["properties"]["Tags"], json!({ "multi_select": [{ "name": "rust" }, { "name": "a b" }] }));
+        let body: Value = serde_json::from_str(&requests[3].body).unwrap();
+        assert!(body["properties"].get("Tags").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_create_page_with_cover() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "database", "id": "db-1", "properties": {} })),
+            MockResponse::json(200, json!({ "object": "page", "id": "page-1" })),
+        ])
+        .await;
+        let notion = Notion::new("secret").unwrap().with_base_url(&server.url);
+
+        notion
+            .create_page_with_blocks("db-1", "Trip", &[], Some("https://example.com/beach.jpg"), &[])
+            .await
+            .unwrap();
+
+        let body: Value = serde_json::from_str(&server.requests()[1].body).unwrap();
+        assert_eq!(body["cover"], json!({
+            "type": "external",
+            "external": { "url": "https://example.com/beach.jpg" }
+        }));
     }
 }
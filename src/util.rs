@@ -1,15 +1,88 @@
 use anyhow::{Result, anyhow};
-use reqwest::{Client, StatusCode};
+use futures::future::join_all;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Client, Response, StatusCode};
 use serde_json::Value;
+use std::env;
+use std::fmt;
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::time::sleep;
 
-pub const SEARCH_BY_FILTER_URL: &str = "https://api.notion.com/v1/search";
-pub const CREATE_DATABASE_URL: &str = "https://api.notion.com/v1/databases/";
-pub const CREATE_PAGE_URL: &str = "https://api.notion.com/v1/pages";
+pub const NOTION_API_URL: &str = "https://api.notion.com/v1";
+pub const SEARCH_BY_FILTER_PATH: &str = "/search";
+pub const DATABASES_PATH: &str = "/databases";
+pub const PAGES_PATH: &str = "/pages";
+pub const BLOCKS_PATH: &str = "/blocks";
+pub const USERS_PATH: &str = "/users";
+
+/// Upper bound in seconds for a single Notion request unless `NOTION_TIMEOUT_SECS` is set,
+/// so a hung connection can't block the server
+pub const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
+
+/// Notion API version sent with every request unless `NOTION_VERSION` is set
+pub const DEFAULT_NOTION_VERSION: &str = "2022-06-28";
+
+/// Retries for a rate limited (429) request unless `NOTION_MAX_RETRIES` is set
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// First backoff step when Notion rate limits without a `Retry-After` header
+pub const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Longest backoff step between retries without a `Retry-After` header
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Independent pages worked on at once unless `JOTDOWN_CONCURRENCY` is set
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+static HTTP_CLIENT: OnceLock<Client> = OnceLock::new();
 
 pub enum ReqMethod {
     Get,
     Post,
-    Patch
+    Patch,
+    Put,
+    Delete,
+}
+
+impl ReqMethod {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ReqMethod::Get => "GET",
+            ReqMethod::Post => "POST",
+            ReqMethod::Patch => "PATCH",
+            ReqMethod::Put => "PUT",
+            ReqMethod::Delete => "DELETE",
+        }
+    }
+}
+
+/// Error reported by the Notion API for a non-success status code
+#[derive(Debug)]
+pub struct NotionError {
+    pub status: StatusCode,
+    pub code: String,
+    pub message: String,
+}
+
+impl fmt::Display for NotionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "notion api error {} ({}): {}", self.status.as_u16(), self.code, self.message)
+    }
+}
+
+impl std::error::Error for NotionError {}
+
+/// Shared HTTP client, built once so connections and TLS sessions are pooled
+pub fn http_client() -> &'static Client {
+    HTTP_CLIENT.get_or_init(|| {
+        Client::builder()
+            .timeout(request_timeout())
+            .build()
+            .unwrap_or_else(|_| Client::new())
+    })
 }
 
 pub async fn send_request(
@@ -18,50 +91,427 @@ pub async fn send_request(
     body: Option<serde_json::Value>,
     auth_token: &str,
 ) -> Result<(StatusCode, Value)> {
-    let client = Client::new();
-    match method {
-        ReqMethod::Get => {
-            let response = client
-                .get(url)
-                .header("Notion-Version", "2022-06-28")
-                .header("Authorization", auth_token)
-                .send()
-                .await?;
-            let status = response.status();
-            let json_result = response.json::<Value>().await?;
-            Ok((status, json_result))
+    send_request_with_timeout(url, method, body, auth_token, request_timeout()).await
+}
+
+async fn send_request_with_timeout(
+    url: &str,
+    method: ReqMethod,
+    body: Option<serde_json::Value>,
+    auth_token: &str,
+    timeout: Duration,
+) -> Result<(StatusCode, Value)> {
+    let client = http_client();
+    let max_retries = max_retries();
+    let notion_version = notion_version();
+    let log_bodies = log_bodies();
+    let mut attempt = 0;
+    loop {
+        if log_bodies {
+            tracing::debug!(
+                method = method.as_str(),
+                endpoint = endpoint(url),
+                headers = %request_headers(&notion_version),
+                body = %redact(&body.as_ref().map(|b| b.to_string()).unwrap_or_default(), auth_token),
+                "notion request body"
+            );
         }
-        ReqMethod::Post => {
-            if let Some(req_body) = body {
-                let response = client
-                    .post(url)
-                    .header("Notion-Version", "2022-06-28")
-                    .header("Authorization", auth_token)
-                    .json(&req_body)
-                    .send()
-                    .await?;
-                let status = response.status();
-                let json_result = response.json::<Value>().await?;
-                Ok((status, json_result))
-            } else {
-                Err(anyhow!("request body is missing"))
+        let request = match method {
+            ReqMethod::Get => client.get(url),
+            ReqMethod::Delete => client.delete(url),
+            ReqMethod::Post | ReqMethod::Patch | ReqMethod::Put => {
+                let Some(req_body) = &body else {
+                    return Err(anyhow!("request body is missing"));
+                };
+                let builder = match method {
+                    ReqMethod::Post => client.post(url),
+                    ReqMethod::Put => client.put(url),
+                    _ => client.patch(url),
+                };
+                builder.json(req_body)
             }
+        };
+        let response = request
+            .header("Notion-Version", notion_version.as_str())
+            .header("Authorization", auth_token)
+            .timeout(timeout)
+            .send()
+            .await
+            .map_err(|e| {
+                tracing::error!(method = method.as_str(), endpoint = endpoint(url), "notion request failed: {}", e);
+                request_error(e, timeout)
+            })?;
+        tracing::info!(
+            method = method.as_str(),
+            endpoint = endpoint(url),
+            status = response.status().as_u16(),
+            attempt,
+            "notion request"
+        );
+
+        if response.status() == StatusCode::TOO_MANY_REQUESTS && attempt < max_retries {
+            let delay = retry_delay(response.headers(), attempt);
+            tracing::warn!("rate limited by notion, retrying in {:?}", delay);
+            sleep(delay).await;
+            attempt += 1;
+            continue;
         }
-        ReqMethod::Patch => {
-            if let Some(req_body) = body {
-                let response = client
-                    .patch(url)
-                    .header("Notion-Version", "2022-06-28")
-                    .header("Authorization", auth_token)
-                    .json(&req_body)
-                    .send()
-                    .await?;
-                let status = response.status();
-                let json_result = response.json::<Value>().await?;
-                Ok((status, json_result))
-            } else {
-                Err(anyhow!("request body is missing"))
+        return read_response(response, timeout, log_bodies.then_some(auth_token)).await;
+    }
+}
+
+/// Path and query of a request url, which is all that differs between Notion calls
+fn endpoint(url: &str) -> &str {
+    url.find("://")
+        .and_then(|scheme_end| url[scheme_end + 3..].find('/').map(|i| &url[scheme_end + 3 + i..]))
+        .unwrap_or(url)
+}
+
+/// Number of independent pages processed at once, from `JOTDOWN_CONCURRENCY`
+pub fn concurrency() -> usize {
+    concurrency_from(env::var("JOTDOWN_CONCURRENCY").ok())
+}
+
+fn concurrency_from(value: Option<String>) -> usize {
+    value
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|limit| *limit > 0)
+        .unwrap_or(DEFAULT_CONCURRENCY)
+}
+
+/// Run `task` for every item with at most `limit` running at once
+///
+/// Only use this for independent pages: the order requests are sent in is
+/// not preserved, so batches appended to the same page must stay sequential.
+/// Results are returned in the order of `items`.
+pub async fn run_bounded<T, R, F, Fut>(items: impl IntoIterator<Item = T>, limit: usize, task: F) -> Vec<R>
+where
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = R>,
+{
+    let semaphore = Semaphore::new(limit.max(1));
+    let semaphore = &semaphore;
+    let task = &task;
+    join_all(items.into_iter().map(|item| async move {
+        let _permit = semaphore.acquire().await.expect("the semaphore is never closed");
+        task(item).await
+    }))
+    .await
+}
+
+/// Timeout for a single Notion request, from `NOTION_TIMEOUT_SECS`
+fn request_timeout() -> Duration {
+    let secs = env::var("NOTION_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_REQUEST_TIMEOUT_SECS);
+    Duration::from_secs(secs)
+}
+
+/// Give timeouts a message that names the Notion API instead of a bare reqwest error
+fn request_error(error: reqwest::Error, timeout: Duration) -> anyhow::Error {
+    if error.is_timeout() {
+        anyhow!("the Notion API timed out after {}s, try again later", timeout.as_secs_f32())
+    } else {
+        error.into()
+    }
+}
+
+/// Notion API version to request, from `NOTION_VERSION`
+fn notion_version() -> String {
+    env::var("NOTION_VERSION")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_NOTION_VERSION.to_string())
+}
+
+/// Whether request and response bodies are logged at DEBUG, from `JOTDOWN_LOG_BODIES`
+fn log_bodies() -> bool {
+    flag_from(env::var("JOTDOWN_LOG_BODIES").ok())
+}
+
+fn flag_from(value: Option<String>) -> bool {
+    value.is_some_and(|v| matches!(v.trim().to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+}
+
+/// Placeholder logged instead of the integration secret
+const REDACTED: &str = "[REDACTED]";
+
+/// Headers sent with a request as logged, the Authorization value is never included
+fn request_headers(notion_version: &str) -> String {
+    format!("Authorization: {}, Notion-Version: {}", REDACTED, notion_version)
+}
+
+/// Replace the integration secret of `auth_token` wherever it appears in `text`
+fn redact(text: &str, auth_token: &str) -> String {
+    let secret = auth_token.trim().trim_start_matches("Bearer").trim();
+    if secret.is_empty() {
+        return text.to_string();
+    }
+    text.replace(secret, REDACTED)
+}
+
+/// Maximum number of retries for a rate limited request, from `NOTION_MAX_RETRIES`
+fn max_retries() -> u32 {
+    env::var("NOTION_MAX_RETRIES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_RETRIES)
+}
+
+/// How long to wait before retrying a rate limited request
+///
+/// Honors the `Retry-After` header when Notion sends one, otherwise backs
+/// off exponentially from [`RETRY_BASE_DELAY`] with full jitter, a random
+/// delay up to the backoff step, so concurrent uploads don't retry in lockstep.
+fn retry_delay(headers: &HeaderMap, attempt: u32) -> Duration {
+    headers
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or_else(|| {
+            let ceiling = backoff_ceiling(attempt).as_millis() as u64;
+            Duration::from_millis(rand::random_range(0..=ceiling))
+        })
+}
+
+/// Exponential backoff step of a retry, capped at [`MAX_RETRY_DELAY`]
+fn backoff_ceiling(attempt: u32) -> Duration {
+    RETRY_BASE_DELAY
+        .checked_mul(2u32.saturating_pow(attempt))
+        .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY))
+}
+
+/// Read and parse a response, logging its body redacted with `auth_token` when given
+async fn read_response(response: Response, timeout: Duration, log_with: Option<&str>) -> Result<(StatusCode, Value)> {
+    let status = response.status();
+    let text = response.text().await.map_err(|e| request_error(e, timeout))?;
+    if let Some(auth_token) = log_with {
+        tracing::debug!(status = status.as_u16(), body = %redact(&text, auth_token), "notion response body");
+    }
+    match serde_json::from_str::<Value>(&text) {
+        Ok(json_result) => check_response(status, json_result),
+        Err(_) if !status.is_success() => check_response(status, Value::String(text)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Turns a non-success Notion response into a [`NotionError`]
+///
+/// The error `code` and `message` are read from the Notion error body,
+/// falling back to the HTTP reason phrase when the body has neither.
+pub fn check_response(status: StatusCode, body: Value) -> Result<(StatusCode, Value)> {
+    if status.is_success() {
+        return Ok((status, body));
+    }
+    let reason = status.canonical_reason().unwrap_or("unknown error");
+    let code = body
+        .get("code")
+        .and_then(|v| v.as_str())
+        .unwrap_or(reason)
+        .to_string();
+    let message = match body.get("message").and_then(|v| v.as_str()) {
+        Some(message) => message.to_string(),
+        None => body.as_str().unwrap_or(reason).to_string(),
+    };
+    Err(NotionError { status, code, message }.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{MockResponse, MockServer};
+    use serde_json::json;
+
+    #[test]
+    fn test_concurrency_from_env_value() {
+        assert_eq!(concurrency_from(None), DEFAULT_CONCURRENCY);
+        assert_eq!(concurrency_from(Some(" 8 ".to_string())), 8);
+        assert_eq!(concurrency_from(Some("0".to_string())), DEFAULT_CONCURRENCY);
+        assert_eq!(concurrency_from(Some("many".to_string())), DEFAULT_CONCURRENCY);
+    }
+
+    #[test]
+    fn test_flag_from_env_value() {
+        assert!(!flag_from(None));
+        assert!(flag_from(Some(" TRUE ".to_string())));
+        assert!(flag_from(Some("1".to_string())));
+        assert!(!flag_from(Some("0".to_string())));
+        assert!(!flag_from(Some("".to_string())));
+    }
+
+    #[test]
+    fn test_logged_bodies_redact_the_token() {
+        let token = "Bearer ntn_secret123";
+        let body = r#"{"object":"error","message":"API token ntn_secret123 is invalid"}"#;
+        let logged = redact(body, token);
+        assert!(!logged.contains("ntn_secret123"));
+        assert_eq!(logged, r#"{"object":"error","message":"API token [REDACTED] is invalid"}"#);
+        assert_eq!(redact(token, token), "Bearer [REDACTED]");
+
+        let headers = request_headers("2022-06-28");
+        assert_eq!(headers, "Authorization: [REDACTED], Notion-Version: 2022-06-28");
+        assert!(!headers.contains("Bearer"));
+    }
+
+    #[tokio::test]
+    async fn test_run_bounded_never_exceeds_limit() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let active = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let results = run_bounded(0..12, 3, |item| {
+            let (active, peak) = (&active, &peak);
+            async move {
+                let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                sleep(Duration::from_millis(10)).await;
+                active.fetch_sub(1, Ordering::SeqCst);
+                item * 2
             }
+        })
+        .await;
+
+        assert_eq!(results, (0..12).map(|i| i * 2).collect::<Vec<_>>());
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_endpoint_strips_scheme_and_host() {
+        assert_eq!(endpoint("https://api.notion.com/v1/pages/abc"), "/v1/pages/abc");
+        assert_eq!(endpoint("http://127.0.0.1:8080/blocks/x/children?page_size=100"), "/blocks/x/children?page_size=100");
+        assert_eq!(endpoint("not a url"), "not a url");
+    }
+
+    #[test]
+    fn test_check_response_success_passes_through() {
+        let body = json!({ "object": "page", "id": "abc" });
+        let (status, value) = check_response(StatusCode::OK, body.clone()).unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(value, body);
+    }
+
+    #[test]
+    fn test_check_response_unauthorized() {
+        let body = json!({
+            "object": "error",
+            "status": 401,
+            "code": "unauthorized",
+            "message": "API token is invalid."
+        });
+        let err = check_response(StatusCode::UNAUTHORIZED, body).unwrap_err();
+        let notion_err = err.downcast_ref::<NotionError>().unwrap();
+        assert_eq!(notion_err.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(notion_err.code, "unauthorized");
+        assert_eq!(notion_err.message, "API token is invalid.");
+        assert!(err.to_string().contains("unauthorized"));
+    }
+
+    #[test]
+    fn test_check_response_non_json_error_body() {
+        let body = Value::String("Bad Gateway".to_string());
+        let err = check_response(StatusCode::BAD_GATEWAY, body).unwrap_err();
+        let notion_err = err.downcast_ref::<NotionError>().unwrap();
+        assert_eq!(notion_err.code, "Bad Gateway");
+        assert_eq!(notion_err.message, "Bad Gateway");
+    }
+
+    #[test]
+    fn test_retry_delay_prefers_retry_after_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, "2".parse().unwrap());
+        assert_eq!(retry_delay(&headers, 0), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_backoff_ceiling_grows_exponentially() {
+        assert_eq!(backoff_ceiling(0), RETRY_BASE_DELAY);
+        assert_eq!(backoff_ceiling(2), RETRY_BASE_DELAY * 4);
+        assert_eq!(backoff_ceiling(10), MAX_RETRY_DELAY);
+        assert_eq!(backoff_ceiling(u32::MAX), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_retry_delay_jitters_within_backoff() {
+        let headers = HeaderMap::new();
+        for attempt in 0..4 {
+            let delays: Vec<Duration> = (0..200).map(|_| retry_delay(&headers, attempt)).collect();
+            assert!(delays.iter().all(|delay| *delay <= backoff_ceiling(attempt)));
+            assert!(delays.iter().any(|delay| *delay != delays[0]), "delays are not jittered");
         }
     }
+
+    #[tokio::test]
+    async fn test_send_request_retries_after_rate_limit() {
+        let server = MockServer::start(vec![
+            MockResponse::json(429, json!({ "code": "rate_limited", "message": "slow down" }))
+                .with_header("Retry-After", "0"),
+            MockResponse::json(200, json!({ "object": "page", "id": "abc" })),
+        ])
+        .await;
+
+        let (status, value) = send_request(&server.url, ReqMethod::Get, None, "Bearer token")
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(value["id"], "abc");
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|r| r.method == "GET" && r.path == "/" && r.body.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_send_request_delete() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "block", "id": "block-1", "archived": true })),
+        ])
+        .await;
+
+        let (status, value) = send_request(&format!("{}/blocks/block-1", server.url), ReqMethod::Delete, None, "Bearer token")
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(value["archived"], true);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "DELETE");
+        assert_eq!(requests[0].path, "/blocks/block-1");
+        assert!(requests[0].body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_send_request_put_sends_body() {
+        let server = MockServer::start(vec![MockResponse::json(200, json!({ "object": "page" }))]).await;
+
+        send_request(&server.url, ReqMethod::Put, Some(json!({ "archived": false })), "Bearer token")
+            .await
+            .unwrap();
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "PUT");
+        assert_eq!(requests[0].body, r#"{"archived":false}"#);
+
+        let err = send_request(&server.url, ReqMethod::Put, None, "Bearer token").await.unwrap_err();
+        assert_eq!(err.to_string(), "request body is missing");
+    }
+
+    #[tokio::test]
+    async fn test_send_request_times_out() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, json!({ "object": "page" })).with_delay(Duration::from_millis(500)),
+        ])
+        .await;
+
+        let err = send_request_with_timeout(
+            &server.url,
+            ReqMethod::Get,
+            None,
+            "Bearer token",
+            Duration::from_millis(100),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("the Notion API timed out"));
+    }
 }